@@ -1,9 +1,12 @@
-use super::super::data_and_config::neural_net_config_parts::{NeuralNetSettings, LayerSettings};
-use super::classifier_errors::{
-    ga_missing_weights_error, 
-    sa_missing_weights_error
+use super::super::data_and_config::neural_net_config_parts::{
+    NeuralNetSettings,
+    LayerSettings,
+    ScheduleSettings
 };
-use rand::Rng;
+use super::super::neural_network_parts::optimizer::OPTIMIZER_NAMES;
+use super::super::tinman_error::TinmanError;
+use super::super::neural_network_parts::random::{make_rng, seeded_rng};
+use rand::{Rng, StdRng};
 
 pub struct Breeder {
     max_annealing_temp : f64,
@@ -23,7 +26,13 @@ pub struct Breeder {
     alter_momentum : bool,
     alter_upper_weight_limit : bool,
     alter_lower_weight_limit : bool,
-    swap_weights : bool
+    alter_optimizer : bool,
+    alter_schedule : bool,
+    alter_l1 : bool,
+    alter_l2 : bool,
+    alter_max_norm : bool,
+    swap_weights : bool,
+    rng : StdRng
 }
 
 impl Breeder {
@@ -46,16 +55,24 @@ impl Breeder {
             alter_momentum : true,
             alter_upper_weight_limit : true,
             alter_lower_weight_limit : true,
-            swap_weights : false
+            alter_optimizer : true,
+            alter_schedule : true,
+            alter_l1 : true,
+            alter_l2 : true,
+            alter_max_norm : true,
+            swap_weights : false,
+            rng : make_rng(None)
         }
     }
 
+    pub fn reseed(&mut self, seed : u64) {
+        self.rng = seeded_rng(seed);
+    }
+
     pub fn child_w_sa_hyper_parameters(&mut self, child : &mut NeuralNetSettings, parent : &mut NeuralNetSettings) {
-        let mut rng = rand::thread_rng();
-        let mut rand_float = rand::thread_rng();
         for j in 0 .. child.layers.len() {
-            let mutation : f64 = rand_float.gen_range(0.0, self.current_annealing_temp);
-            let rand : usize = rng.gen_range(0, 1);
+            let mutation : f64 = self.rng.gen_range(0.0, self.current_annealing_temp);
+            let rand : usize = self.rng.gen_range(0, 2);
             let mut sign : f64 = 1.0;
             if rand == 0 {
                 sign = -1.0;
@@ -98,6 +115,17 @@ impl Breeder {
                     child.layers[j].weight_range[1] = child.layers[j].weight_range[0] + 0.1;
                 }
             }
+            // regularization is only tuned on layers that already use it,
+            // the search never turns it on or off
+            if self.alter_l1 == true {
+                child.layers[j].l1 = scale_optional(parent.layers[j].l1, sign * mutation);
+            }
+            if self.alter_l2 == true {
+                child.layers[j].l2 = scale_optional(parent.layers[j].l2, sign * mutation);
+            }
+            if self.alter_max_norm == true {
+                child.layers[j].max_norm = scale_optional(parent.layers[j].max_norm, sign * mutation);
+            }
         }
         //child.config_id = self.current_config_id;
         self.current_config_id += 1;
@@ -142,48 +170,140 @@ impl Breeder {
                     child.layers[j].weight_range[1] = child.layers[j].weight_range[0] + 0.1;
                 }
             }
+            if self.alter_l1 == true {
+                child.layers[j].l1 = blend_optional(parent1.layers[j].l1, parent2.layers[j].l1, favoured);
+            }
+            if self.alter_l2 == true {
+                child.layers[j].l2 = blend_optional(parent1.layers[j].l2, parent2.layers[j].l2, favoured);
+            }
+            if self.alter_max_norm == true {
+                child.layers[j].max_norm = blend_optional(
+                    parent1.layers[j].max_norm,
+                    parent2.layers[j].max_norm,
+                    favoured
+                );
+            }
+            if self.alter_optimizer == true {
+                self.inherit_optimizer(
+                    &mut child.layers[j],
+                    &parent1.layers[j],
+                    &parent2.layers[j]
+                );
+            }
+            if self.alter_schedule == true {
+                self.blend_schedules(
+                    &mut child.layers[j],
+                    &parent1.layers[j],
+                    &parent2.layers[j]
+                );
+            }
         }
         //child.config_id = self.current_config_id;
         self.current_config_id += 1;
     }
 
+    // Schedule parameters are only blended when both parents use the same schedule,
+    // otherwise the child keeps the favoured parent's schedule as is.
+    // Round counts are blended like the rest and rounded, the SA step leaves schedules alone.
+    fn blend_schedules(
+        &mut self,
+        child_layer : &mut LayerSettings,
+        parent1_layer : &LayerSettings,
+        parent2_layer : &LayerSettings
+    )
+    {
+        let favoured : f64 = self.current_p1_favourability;
+        match (&parent1_layer.learning_rate_schedule, &parent2_layer.learning_rate_schedule) {
+            (Some(p1_schedule), Some(p2_schedule)) if p1_schedule.schedule == p2_schedule.schedule => {
+                let mut child_schedule : ScheduleSettings = p1_schedule.clone();
+                child_schedule.decay_rate = blend_optional(p1_schedule.decay_rate, p2_schedule.decay_rate, favoured);
+                child_schedule.min_learning_rate = blend_optional(
+                    p1_schedule.min_learning_rate,
+                    p2_schedule.min_learning_rate,
+                    favoured
+                );
+                child_schedule.step_rounds = blend_optional_count(p1_schedule.step_rounds, p2_schedule.step_rounds, favoured);
+                child_schedule.patience = blend_optional_count(p1_schedule.patience, p2_schedule.patience, favoured);
+                child_schedule.cycle_multiplier = blend_optional(
+                    p1_schedule.cycle_multiplier,
+                    p2_schedule.cycle_multiplier,
+                    favoured
+                );
+                child_layer.learning_rate_schedule = Some(child_schedule);
+            } _ => {
+                child_layer.learning_rate_schedule = parent1_layer.learning_rate_schedule.clone();
+            }
+        }
+    }
+
+    // The child usually takes the favoured parent's optimizer, otherwise the other parent's,
+    // and now and then one neither parent uses, so new optimizers get tried.
+    fn inherit_optimizer(
+        &mut self,
+        child_layer : &mut LayerSettings,
+        parent1_layer : &LayerSettings,
+        parent2_layer : &LayerSettings
+    )
+    {
+        let favoured : f64 = self.current_p1_favourability;
+        let pick : f64 = self.rng.gen_range(0.0, 1.0);
+        if pick < favoured {
+            child_layer.optimizer = parent1_layer.optimizer.clone();
+        } else if pick < favoured + (1.0 - favoured) / 2.0 {
+            child_layer.optimizer = parent2_layer.optimizer.clone();
+        } else {
+            let optimizer_idx : usize = self.rng.gen_range(0, OPTIMIZER_NAMES.len());
+            child_layer.optimizer = Some(OPTIMIZER_NAMES[optimizer_idx].to_string());
+        }
+        child_layer.beta1 = blend_optional(parent1_layer.beta1, parent2_layer.beta1, favoured);
+        child_layer.beta2 = blend_optional(parent1_layer.beta2, parent2_layer.beta2, favoured);
+        child_layer.epsilon = blend_optional(parent1_layer.epsilon, parent2_layer.epsilon, favoured);
+    }
+
     pub fn child_w_sa_weights(
         &mut self,
         child : &mut NeuralNetSettings,
         parent : &mut NeuralNetSettings
-    )
+    ) -> Result<(), TinmanError>
     {
         for j in 0 .. child.layers.len() {
             match &mut child.layers[j].layer_weights {
                 Some(weight_vecs) => {
                     self.match_p1_weights_sa(
                         weight_vecs, 
-                        &mut parent.layers[j]
-                    );
+                        &mut parent.layers[j],
+                        j
+                    )?;
                 } None => {
-                    panic!(sa_missing_weights_error());
+                    return Err(TinmanError::MissingWeights { layer : j });
                 }
             }
         }
         //child.config_id = self.current_config_id;
         self.current_config_id += 1;
+        Ok(())
     }
 
     fn match_p1_weights_sa(
         &mut self,
         weight_vecs : &mut Vec<Vec<f64>>, 
-        parent1_layer : &mut LayerSettings
-    )
+        parent1_layer : &mut LayerSettings,
+        layer : usize
+    ) -> Result<(), TinmanError>
     {
-        let mut rng = rand::thread_rng();
-        let mut rand_float = rand::thread_rng();
+        // gamma of a batch norm layer is left to training, it is copied over unchanged
+        let is_batch_norm : bool = parent1_layer.is_batch_norm();
         match &mut parent1_layer.layer_weights {
             Some(parent_weight_vecs) => {
                 for k in 0 .. weight_vecs.len() {
                     for l in 0 .. weight_vecs[k].len() {
+                        if is_batch_norm {
+                            weight_vecs[k][l] = parent_weight_vecs[k][l];
+                            continue;
+                        }
                         let current_temp : f64 = self.current_annealing_temp;
-                        let mutation : f64 = rand_float.gen_range(0.0, current_temp);
-                        let rand : usize = rng.gen_range(0, 1);
+                        let mutation : f64 = self.rng.gen_range(0.0, current_temp);
+                        let rand : usize = self.rng.gen_range(0, 1);
                         let mut sign : f64 = 1.0;
                         if rand == 0 {
                             sign = -1.0;
@@ -191,8 +311,9 @@ impl Breeder {
                         weight_vecs[k][l] = parent_weight_vecs[k][l] + sign * mutation;
                     }
                 }
+                Ok(())
             } None => {
-                panic!(sa_missing_weights_error());
+                Err(TinmanError::MissingWeights { layer })
             }
         }
     }
@@ -202,11 +323,12 @@ impl Breeder {
         child : &mut NeuralNetSettings,
         parent1 : &NeuralNetSettings, 
         parent2 : &NeuralNetSettings
-    )
+    ) -> Result<(), TinmanError>
     {
         self.swap_weights = true;
-        self.child_w_ga_weights(child, parent1, parent2);
+        let result = self.child_w_ga_weights(child, parent1, parent2);
         self.swap_weights = false;
+        result
     }
 
     pub fn child_w_ga_weights(
@@ -214,7 +336,7 @@ impl Breeder {
         child : &mut NeuralNetSettings,
         parent1 : &NeuralNetSettings, 
         parent2 : &NeuralNetSettings
-    )
+    ) -> Result<(), TinmanError>
     {
         for j in 0 .. child.layers.len() {
             match &mut child.layers[j].layer_weights {
@@ -222,33 +344,37 @@ impl Breeder {
                     self.match_p1_weights_ga(
                         weight_vecs, 
                         &parent1.layers[j], 
-                        &parent2.layers[j]
-                    );
+                        &parent2.layers[j],
+                        j
+                    )?;
                 } None => {
-                    panic!(ga_missing_weights_error());
+                    return Err(TinmanError::MissingWeights { layer : j });
                 }
             }
         }
         //child.config_id = self.current_config_id;
         self.current_config_id += 1;
+        Ok(())
     }
 
     fn match_p1_weights_ga(
         &mut self, 
         weight_vecs : &mut Vec<Vec<f64>>, 
         parent1_layer : &LayerSettings, 
-        parent2_layer : &LayerSettings
-    )
+        parent2_layer : &LayerSettings,
+        layer : usize
+    ) -> Result<(), TinmanError>
     {
         match &parent1_layer.layer_weights {
             Some(p1_weight_vecs) => {
                 self.match_p2_weights(
                     weight_vecs, 
                     p1_weight_vecs, 
-                    parent2_layer
-                );
+                    parent2_layer,
+                    layer
+                )
             } None => {
-                panic!(ga_missing_weights_error());
+                Err(TinmanError::MissingWeights { layer })
             }
         }
     }
@@ -257,16 +383,16 @@ impl Breeder {
         &mut self,
         weight_vecs : &mut Vec<Vec<f64>>,
         parent1_weights: &Vec<Vec<f64>>,
-        parent2_layer : &LayerSettings
-    )
+        parent2_layer : &LayerSettings,
+        layer : usize
+    ) -> Result<(), TinmanError>
     {
-        let mut rand_float = rand::thread_rng();
         match &parent2_layer.layer_weights {
             Some(p2_weight_vecs) => {
                 for k in 0 .. weight_vecs.len() {
                     let favourable : f64 = self.current_p1_favourability;
                     if self.swap_weights{
-                        let p2_preference : f64 = rand_float.gen_range(0.0, 1.0);
+                        let p2_preference : f64 = self.rng.gen_range(0.0, 1.0);
                         for l in 0 .. weight_vecs[k].len() {
                             if p2_preference < favourable {
                                 weight_vecs[k][l] = parent1_weights[k][l];
@@ -282,8 +408,9 @@ impl Breeder {
                         }
                     }
                 }
+                Ok(())
             } None => {
-                panic!(ga_missing_weights_error());
+                Err(TinmanError::MissingWeights { layer })
             }
         }
     }
@@ -350,3 +477,21 @@ impl Breeder {
     }
 }
 
+fn blend_optional(parent1_value : Option<f64>, parent2_value : Option<f64>, favoured : f64) -> Option<f64> {
+    match (parent1_value, parent2_value) {
+        (Some(p1_value), Some(p2_value)) => Some(p1_value * favoured + p2_value * (1.0 - favoured)),
+        (Some(p1_value), None) => Some(p1_value),
+        (None, p2_value) => p2_value
+    }
+}
+
+fn blend_optional_count(parent1_value : Option<usize>, parent2_value : Option<usize>, favoured : f64) -> Option<usize> {
+    blend_optional(parent1_value.map(|value| value as f64), parent2_value.map(|value| value as f64), favoured)
+        .map(|value| value.round() as usize)
+}
+
+// Regularization values span orders of magnitude, so they are mutated on a log scale:
+// a change of up to 1 multiplies or divides the value by at most e, and it stays positive.
+fn scale_optional(parent_value : Option<f64>, change : f64) -> Option<f64> {
+    parent_value.map(|value| value * change.exp())
+}
@@ -1,9 +0,0 @@
-#[allow(dead_code)]
-pub fn ga_missing_weights_error() -> String {
-    "Genetic algoritm for weights must be done on neural network layers containing weight vectors!".to_string()
-}
-
-#[allow(dead_code)]
-pub fn sa_missing_weights_error() -> String {
-    "Simulated Annealing for weights must be done on neural network layers containing weight vectors!".to_string()  
-}
\ No newline at end of file
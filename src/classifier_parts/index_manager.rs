@@ -1,35 +1,46 @@
-use rand::Rng;
+use rand::{Rng, StdRng};
+use super::super::neural_network_parts::random::{make_rng, seeded_rng};
+use super::super::tinman_error::TinmanError;
 
 pub struct IndexManager {
     path : Vec<usize>,
     current_idx : usize,
-    max_index : usize
+    max_index : usize,
+    rng : StdRng
 }
 
 impl IndexManager {
-    pub fn new() -> IndexManager {
+    pub fn new(seed : Option<u64>) -> IndexManager {
         IndexManager {
             path : Vec::new(),
             current_idx : 0,
-            max_index : 0
+            max_index : 0,
+            rng : make_rng(seed)
         }
     }
 
-    pub fn update_random_path_len(&mut self, path_len: usize) {
+    pub fn reseed(&mut self, seed : u64) {
+        self.rng = seeded_rng(seed);
+    }
+
+    // a path of no rows has nothing to hand out, so there is nothing to train on
+    pub fn update_random_path_len(&mut self, path_len: usize) -> Result<(), TinmanError> {
+        if path_len < 1 {
+            return Err(TinmanError::NoTrainingRows);
+        }
         let current_len : usize = self.path.len();
         if path_len > current_len {
             for _ in current_len .. path_len {
                 self.path.push(0);
             }
             self.max_index = self.path.len()-1;
-        } else if path_len < 1 {
-            panic!("Invalid path length for Index Manager");
         } else if path_len < current_len {
             self.max_index = path_len - 1;
         }
         for i in 0 .. self.max_index {
             self.path[i] = i;
         }
+        Ok(())
     }
 
     pub fn has_next(&self) -> bool {
@@ -43,9 +54,8 @@ impl IndexManager {
     }
 
     pub fn reset(&mut self) {
-        let mut rng = rand::thread_rng();
         for i in 0 .. self.max_index {
-            let rand : usize = rng.gen_range(0, self.max_index);
+            let rand : usize = self.rng.gen_range(0, self.max_index);
             let temp : usize = self.path[rand];
             self.path[rand] = self.path[i];
             self.path[i] = temp;
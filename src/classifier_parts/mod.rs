@@ -1,5 +1,4 @@
 pub mod nnet_trainer;
 pub mod index_manager;
 pub mod breeder;
-pub mod classifier_errors;
 pub mod optimize_nnet;
\ No newline at end of file
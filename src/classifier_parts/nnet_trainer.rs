@@ -1,15 +1,65 @@
 use super::super::neural_network_parts::neural_network::NeuralNetwork;
 use super::super::data_and_config::neural_net_config_parts::NeuralNetSettings;
 use super::super::data_and_config::dataset::{DataSet, Row};
+use super::super::data_and_config::model_file::{
+    save_json,
+    load_json,
+    save_binary,
+    load_binary,
+    NNET_TRAINER_KIND
+};
+use super::super::neural_network_parts::random::derive_seed;
+use super::super::tinman_error::TinmanError;
 use super::index_manager::IndexManager;
+use serde_derive::{Serialize, Deserialize};
+
+use std::path::Path;
+
+// What gets written to disk for a trainer, the network plus how it is being trained.
+#[derive(Serialize, Deserialize)]
+struct NNetTrainerState {
+    settings : NeuralNetSettings,
+    rounds : usize,
+    train_test_boundary : usize,
+    batch_size : Option<usize>,
+    clip_value : Option<f64>,
+    clip_norm : Option<f64>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct RegressionMetrics {
+    pub rmse : f64,
+    pub mae : f64,
+    pub r_squared : f64
+}
+
+// Hamming loss is the fraction of (row, class) pairs predicted wrong,
+// subset accuracy the fraction of rows with exactly the right set of labels.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct MultiLabelMetrics {
+    pub hamming_loss : f64,
+    pub subset_accuracy : f64,
+    pub micro_f1 : f64,
+    pub macro_f1 : f64
+}
 
 pub struct NNetTrainer {
     rand_index : IndexManager,
     trainee : NeuralNetwork,
     rounds : usize,
     train_test_boundary : usize,
+    batch_size : usize,
+    clip_value : Option<f64>,
+    clip_norm : Option<f64>,
+    diverged : bool,
+    rounds_completed : usize,
     correct : usize,
-    dataset_length : usize
+    dataset_length : usize,
+    test_loss : f64,
+    regression_metrics : RegressionMetrics,
+    multi_label_metrics : MultiLabelMetrics,
+    train_losses : Vec<f64>,
+    test_losses : Vec<f64>
 }
 
 impl NNetTrainer {
@@ -17,66 +67,393 @@ impl NNetTrainer {
         settings: &NeuralNetSettings, 
         train_cutoff : usize, 
         rounds : usize
-    ) -> NNetTrainer 
+    ) -> Result<NNetTrainer, TinmanError>
     {
-        NNetTrainer {
-            rand_index : IndexManager::new(),
-            trainee : NeuralNetwork::new(settings),
+        Ok(NNetTrainer {
+            rand_index : IndexManager::new(get_shuffle_seed(settings)),
+            trainee : NeuralNetwork::new(settings)?,
             rounds : rounds,
             train_test_boundary : train_cutoff,
+            batch_size : 1,
+            clip_value : None,
+            clip_norm : None,
+            diverged : false,
+            rounds_completed : 0,
             correct : 0,
-            dataset_length : 0
-        }
+            dataset_length : 0,
+            test_loss : 0.0,
+            regression_metrics : RegressionMetrics {
+                rmse : 0.0,
+                mae : 0.0,
+                r_squared : 0.0
+            },
+            multi_label_metrics : MultiLabelMetrics {
+                hamming_loss : 0.0,
+                subset_accuracy : 0.0,
+                micro_f1 : 0.0,
+                macro_f1 : 0.0
+            },
+            train_losses : Vec::new(),
+            test_losses : Vec::new()
+        })
     }
 
-    pub fn train(&mut self, dataset : &DataSet) {
+    // Stops early, with has_diverged set, once a weight or activation is NaN or infinite.
+    pub fn train(&mut self, dataset : &DataSet) -> Result<(), TinmanError> {
+        if self.train_test_boundary == 0 || dataset.data.is_empty() {
+            return Err(TinmanError::NoTrainingRows);
+        }
+        if self.batch_size < 2 && self.trainee.has_batch_norm() {
+            return Err(TinmanError::BatchNormNeedsBatch { batch_size : self.batch_size });
+        }
         if self.train_test_boundary > dataset.data.len() {
-            self.rand_index.update_random_path_len(dataset.data.len());
+            self.rand_index.update_random_path_len(dataset.data.len())?;
         } else {
-            self.rand_index.update_random_path_len(self.train_test_boundary);
+            self.rand_index.update_random_path_len(self.train_test_boundary)?;
         }
         self.dataset_length = dataset.data.len();
-        let mut prev_accuracy : f64 = 0.0;
+        self.train_losses.clear();
+        self.test_losses.clear();
+        self.diverged = false;
         for _ in 0 .. self.rounds {
+            self.trainee.set_training(true);
+            self.trainee.schedule_learning_rates(self.rounds_completed);
             self.rand_index.reset();
-            while self.rand_index.has_next() {
-                let index : usize = self.rand_index.next();
-                self.train_on_row(index, dataset);
+            let mut round_loss : f64 = 0.0;
+            let mut rows_trained : usize = 0;
+            while self.rand_index.has_next() && !self.diverged {
+                // the last batch of the round may be short
+                let mut batch : Vec<usize> = Vec::with_capacity(self.batch_size);
+                while batch.len() < self.batch_size && self.rand_index.has_next() {
+                    batch.push(self.rand_index.next());
+                }
+                round_loss += self.train_on_batch(&batch, dataset)?;
+                rows_trained += batch.len();
+                if !self.trainee.has_finite_activations() {
+                    self.diverged = true;
+                } else {
+                    self.update_weights();
+                }
+            }
+            self.trainee.set_training(false);
+            // nothing more can be learned once a value is NaN or infinite
+            if self.diverged {
+                self.trainee.discard_gradients();
+                break;
             }
+            let train_loss : f64 = round_loss / rows_trained as f64;
+            self.train_losses.push(train_loss);
+            // schedules watch the test loss, or the training loss if there are no test rows
+            if self.train_test_boundary < dataset.data.len() {
+                let test_loss : f64 = self.mean_test_loss(dataset)?;
+                self.test_losses.push(test_loss);
+                self.trainee.record_loss(test_loss);
+            } else {
+                self.trainee.record_loss(train_loss);
+            }
+            self.rounds_completed += 1;
+        }
+        Ok(())
+    }
+
+    fn update_weights(&mut self) {
+        self.trainee.clip_gradients(self.clip_value, self.clip_norm);
+        self.trainee.apply_gradients();
+        if !self.trainee.has_finite_weights() {
+            self.diverged = true;
+        }
+    }
+
+    // Runs the rows through the trainee as one batch and returns their summed loss,
+    // measured before the weights are updated. The gradients are only accumulated,
+    // train applies them once the batch is done.
+    fn train_on_batch(&mut self, indices : &[usize], dataset : &DataSet) -> Result<f64, TinmanError> {
+        let mut inputs : Vec<&[f64]> = Vec::with_capacity(indices.len());
+        let mut targets : Vec<Vec<f64>> = Vec::with_capacity(indices.len());
+        for index in indices {
+            let row : &Row = &dataset.data[*index];
+            // targets and labels are checked before the loss reads them
+            self.trainee.check_inputs(&row.columns).map_err(|error| error.at_row(*index))?;
+            self.check_row_answers(row).map_err(|error| error.at_row(*index))?;
+            inputs.push(&row.columns);
+            targets.push(self.get_row_targets(row));
+        }
+        self.trainee.forward_rows(&inputs)?;
+        self.trainee.set_error_deltas(&targets)?;
+        let loss : f64 = self.trainee.get_loss_for_rows(&targets);
+        self.trainee.accumulate_gradients_for_rows(&inputs)?;
+        Ok(loss)
+    }
+
+    // what the outputs of the trainee are trained towards for a row
+    fn get_row_targets(&self, row : &Row) -> Vec<f64> {
+        if self.trainee.is_regression() {
+            row.targets.clone()
+        } else if self.trainee.is_multi_label() {
+            self.trainee.multi_hot_targets(&row.labels)
+        } else {
+            self.trainee.multi_hot_targets(&[row.label])
+        }
+    }
+
+    fn mean_test_loss(&mut self, dataset : &DataSet) -> Result<f64, TinmanError> {
+        let outputs : Vec<Vec<f64>> = self.get_test_outputs(dataset)?;
+        let mut total_loss : f64 = 0.0;
+        for (row, row_outputs) in dataset.data[self.train_test_boundary ..].iter().zip(&outputs) {
+            total_loss += self.get_test_row_loss(row, row_outputs);
+        }
+        Ok(total_loss / (dataset.data.len() - self.train_test_boundary) as f64)
+    }
+
+    // Checks every test row and then runs them all through the trainee as one batch,
+    // the outputs line up with the rows after the boundary.
+    fn get_test_outputs(&mut self, dataset : &DataSet) -> Result<Vec<Vec<f64>>, TinmanError> {
+        let test_rows : &[Row] = &dataset.data[self.train_test_boundary ..];
+        let mut inputs : Vec<&[f64]> = Vec::with_capacity(test_rows.len());
+        for (i, row) in test_rows.iter().enumerate() {
+            let index : usize = self.train_test_boundary + i;
+            self.trainee.check_inputs(&row.columns).map_err(|error| error.at_row(index))?;
+            self.check_row_answers(row).map_err(|error| error.at_row(index))?;
+            inputs.push(&row.columns);
         }
+        self.trainee.forward_batch(&inputs)
     }
 
-    fn train_on_row(&mut self, index : usize, dataset : &DataSet) {
-        let row : &Row = &dataset.data[index];
-        let row_data : &Vec<f64> = &row.columns;
-        let label : usize = row.label;
-        self.trainee.forward(row_data);
-        self.trainee.set_error_delta(label);
-        self.trainee.backward(row_data);
+    fn get_test_row_loss(&self, row : &Row, outputs : &[f64]) -> f64 {
+        self.trainee.get_loss_for_outputs(outputs, &self.get_row_targets(row))
     }
 
-    pub fn test(&mut self, dataset : &DataSet) {
+    // the loss functions index the outputs by label, so the answers are checked first
+    fn check_row_answers(&self, row : &Row) -> Result<(), TinmanError> {
+        if self.trainee.is_regression() {
+            self.trainee.check_targets(&row.targets)
+        } else if self.trainee.is_multi_label() {
+            self.trainee.check_labels(&row.labels)
+        } else {
+            self.trainee.check_labels(&[row.label])
+        }
+    }
+
+    // A diverged trainee's outputs mean nothing, its result is left at the failed score.
+    // A boundary past the end of the data tests on every row, one at the end leaves none to test.
+    pub fn test(&mut self, dataset : &DataSet) -> Result<(), TinmanError> {
         self.correct = 0;
+        if self.diverged {
+            // a diverged network has no meaningful loss, so the one from an earlier test isn't kept
+            self.test_loss = f64::INFINITY;
+            return Ok(());
+        }
         if self.train_test_boundary > dataset.data.len() {
             self.train_test_boundary = 0;
         }
+        if self.train_test_boundary == dataset.data.len() {
+            return Err(TinmanError::NoTestRows);
+        }
         self.dataset_length = dataset.data.len();
-        for i in self.train_test_boundary .. dataset.data.len() {
-            let row : &Row = &dataset.data[i];
-            let row_data : &Vec<f64> = &row.columns;
-            let label : usize = row.label;
-            let prediction : usize = self.trainee.predict(row_data);
-            if prediction == label {
+        if self.trainee.is_regression() {
+            return self.test_regression(dataset);
+        } else if self.trainee.is_multi_label() {
+            return self.test_multi_label(dataset);
+        }
+        let outputs : Vec<Vec<f64>> = self.get_test_outputs(dataset)?;
+        let mut total_loss : f64 = 0.0;
+        for (row, row_outputs) in dataset.data[self.train_test_boundary ..].iter().zip(&outputs) {
+            let prediction : usize = self.trainee.get_prediction_from_outputs(row_outputs)?;
+            total_loss += self.get_test_row_loss(row, row_outputs);
+            if prediction == row.label {
                 self.correct += 1;
             }
         }
+        let test_count : usize = self.dataset_length - self.train_test_boundary;
+        self.test_loss = total_loss / test_count as f64;
+        Ok(())
     }
 
-    pub fn get_test_result(&self) -> f64 {
+    // every output of every test row counts as one value for the metrics
+    fn test_regression(&mut self, dataset : &DataSet) -> Result<(), TinmanError> {
+        let mut total_loss : f64 = 0.0;
+        let mut squared_error : f64 = 0.0;
+        let mut absolute_error : f64 = 0.0;
+        let mut target_sum : f64 = 0.0;
+        let mut target_squared_sum : f64 = 0.0;
+        let mut value_count : usize = 0;
+        let outputs : Vec<Vec<f64>> = self.get_test_outputs(dataset)?;
+        for (row, predictions) in dataset.data[self.train_test_boundary ..].iter().zip(&outputs) {
+            total_loss += self.get_test_row_loss(row, predictions);
+            for (prediction, target) in predictions.iter().zip(&row.targets) {
+                let error : f64 = prediction - target;
+                squared_error += error * error;
+                absolute_error += error.abs();
+                target_sum += target;
+                target_squared_sum += target * target;
+                value_count += 1;
+            }
+        }
         let test_count : usize = self.dataset_length - self.train_test_boundary;
+        self.test_loss = total_loss / test_count as f64;
+        let count : f64 = value_count as f64;
+        let total_sum_of_squares : f64 = target_squared_sum - (target_sum * target_sum) / count;
+        self.regression_metrics = RegressionMetrics {
+            rmse : (squared_error / count).sqrt(),
+            mae : absolute_error / count,
+            r_squared : if total_sum_of_squares > 0.0 {
+                1.0 - squared_error / total_sum_of_squares
+            } else {
+                0.0
+            }
+        };
+        Ok(())
+    }
+
+    // scores each test row's predicted set of labels against its actual labels, class by class
+    fn test_multi_label(&mut self, dataset : &DataSet) -> Result<(), TinmanError> {
+        let class_count : usize = self.trainee.get_output_units();
+        let mut true_positives : Vec<usize> = vec![0; class_count];
+        let mut false_positives : Vec<usize> = vec![0; class_count];
+        let mut false_negatives : Vec<usize> = vec![0; class_count];
+        let mut total_loss : f64 = 0.0;
+        let outputs : Vec<Vec<f64>> = self.get_test_outputs(dataset)?;
+        for (row, row_outputs) in dataset.data[self.train_test_boundary ..].iter().zip(&outputs) {
+            let predictions : Vec<usize> = self.trainee.get_labels_from_outputs(row_outputs);
+            total_loss += self.get_test_row_loss(row, row_outputs);
+            let mut exact_match : bool = true;
+            for class in 0 .. class_count {
+                let predicted : bool = predictions.contains(&class);
+                let actual : bool = row.labels.contains(&class);
+                if predicted && actual {
+                    true_positives[class] += 1;
+                } else if predicted {
+                    false_positives[class] += 1;
+                    exact_match = false;
+                } else if actual {
+                    false_negatives[class] += 1;
+                    exact_match = false;
+                }
+            }
+            if exact_match {
+                self.correct += 1;
+            }
+        }
+        let test_count : usize = self.dataset_length - self.train_test_boundary;
+        self.test_loss = total_loss / test_count as f64;
+
+        let total_tp : usize = true_positives.iter().sum();
+        let total_fp : usize = false_positives.iter().sum();
+        let total_fn : usize = false_negatives.iter().sum();
+        // classes that were never in a row or a prediction are left out of macro F1
+        let mut f1_sum : f64 = 0.0;
+        let mut scored_classes : usize = 0;
+        for class in 0 .. class_count {
+            let counts : usize = 2 * true_positives[class] + false_positives[class] + false_negatives[class];
+            if counts > 0 {
+                f1_sum += (2 * true_positives[class]) as f64 / counts as f64;
+                scored_classes += 1;
+            }
+        }
+        let total_counts : usize = 2 * total_tp + total_fp + total_fn;
+        self.multi_label_metrics = MultiLabelMetrics {
+            hamming_loss : (total_fp + total_fn) as f64 / (test_count * class_count) as f64,
+            subset_accuracy : self.correct as f64 / test_count as f64,
+            micro_f1 : if total_counts > 0 {
+                (2 * total_tp) as f64 / total_counts as f64
+            } else {
+                0.0
+            },
+            macro_f1 : if scored_classes > 0 {
+                f1_sum / scored_classes as f64
+            } else {
+                0.0
+            }
+        };
+        Ok(())
+    }
+
+    // Percent correct for classifiers, multi-label networks count a row as correct
+    // only when every label matches (subset accuracy). Regression networks report R²
+    // as a percentage, so a higher result is still better when the optimizer compares trainers.
+    // 0 for a trainer that diverged or has no test rows, a number the comparisons can still handle.
+    pub fn get_test_result(&self) -> f64 {
+        let test_count : usize = self.dataset_length.saturating_sub(self.train_test_boundary);
+        if self.diverged || test_count == 0 {
+            return 0.0;
+        }
+        if self.trainee.is_regression() {
+            return self.regression_metrics.r_squared * 100.0;
+        }
         (self.correct as f64 / test_count as f64) * 100.0
     }
 
+    // RMSE, MAE and R² from the last call to test, only filled in for regression networks
+    pub fn get_regression_metrics(&self) -> RegressionMetrics {
+        self.regression_metrics
+    }
+
+    // Hamming loss, subset accuracy and F1 scores from the last call to test,
+    // only filled in for multi-label networks
+    pub fn get_multi_label_metrics(&self) -> MultiLabelMetrics {
+        self.multi_label_metrics
+    }
+
+    // mean loss over the test rows from the last call to test, infinite once the trainer has diverged
+    pub fn get_test_loss(&self) -> f64 {
+        self.test_loss
+    }
+
+    // mean loss over the training rows, one entry per round of the last call to train
+    pub fn get_train_losses(&self) -> &Vec<f64> {
+        &self.train_losses
+    }
+
+    // mean loss over the test rows after each round of the last call to train,
+    // empty if every row was used for training
+    pub fn get_test_losses(&self) -> &Vec<f64> {
+        &self.test_losses
+    }
+
+    // Number of rows whose gradients are averaged into each weight update, 1 updates after every row.
+    // The rows of a batch go through the trainee together, batch norm layers need at least 2.
+    pub fn set_batch_size(&mut self, batch_size : usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    pub fn get_batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    // Limits each value of the mean gradient of a batch to ±clip_value before the optimizer
+    // step, None or a limit that isn't above 0 turns it off.
+    pub fn set_clip_value(&mut self, clip_value : Option<f64>) {
+        self.clip_value = clip_value;
+    }
+
+    pub fn get_clip_value(&self) -> Option<f64> {
+        self.clip_value
+    }
+
+    // Scales the mean gradient of a batch down when its length over every layer is
+    // above clip_norm, applied after clip_value.
+    pub fn set_clip_norm(&mut self, clip_norm : Option<f64>) {
+        self.clip_norm = clip_norm;
+    }
+
+    pub fn get_clip_norm(&self) -> Option<f64> {
+        self.clip_norm
+    }
+
+    // True when the last call to train stopped early because a weight or activation
+    // became NaN or infinite, or the trainee was marked failed since.
+    pub fn has_diverged(&self) -> bool {
+        self.diverged
+    }
+
+    // For a trainee that turned out unusable some other way, such as outputs no prediction
+    // can be made from. It is scored like a diverged one until it is trained again.
+    pub fn mark_failed(&mut self) {
+        self.diverged = true;
+        self.correct = 0;
+        self.test_loss = f64::INFINITY;
+    }
+
     pub fn get_train_test_boundary(&self) -> usize {
         self.train_test_boundary
     }
@@ -85,8 +462,67 @@ impl NNetTrainer {
         self.trainee.get_id()
     }
 
-    pub fn update_trainee(&mut self, current_default : &NeuralNetSettings, new_cutoff : usize) {
+    pub fn save<P : AsRef<Path>>(&mut self, path : P) -> Result<(), TinmanError> {
+        save_json(path, NNET_TRAINER_KIND, &self.get_state())?;
+        Ok(())
+    }
+
+    pub fn load<P : AsRef<Path>>(path : P) -> Result<NNetTrainer, TinmanError> {
+        let state : NNetTrainerState = load_json(path, NNET_TRAINER_KIND)?;
+        NNetTrainer::from_state(&state)
+    }
+
+    pub fn save_binary<P : AsRef<Path>>(&mut self, path : P) -> Result<(), TinmanError> {
+        save_binary(path, NNET_TRAINER_KIND, self.get_state(), |state| &mut state.settings)?;
+        Ok(())
+    }
+
+    pub fn load_binary<P : AsRef<Path>>(path : P) -> Result<NNetTrainer, TinmanError> {
+        let state : NNetTrainerState = load_binary(path, NNET_TRAINER_KIND, |state : &mut NNetTrainerState| &mut state.settings)?;
+        NNetTrainer::from_state(&state)
+    }
+
+    fn get_state(&mut self) -> NNetTrainerState {
+        NNetTrainerState {
+            settings : self.trainee.get_settings(),
+            rounds : self.rounds,
+            train_test_boundary : self.train_test_boundary,
+            batch_size : Some(self.batch_size),
+            clip_value : self.clip_value,
+            clip_norm : self.clip_norm
+        }
+    }
+
+    fn from_state(state : &NNetTrainerState) -> Result<NNetTrainer, TinmanError> {
+        let mut trainer = NNetTrainer::new(&state.settings, state.train_test_boundary, state.rounds)?;
+        trainer.set_batch_size(state.batch_size.unwrap_or(1));
+        trainer.set_clip_value(state.clip_value);
+        trainer.set_clip_norm(state.clip_norm);
+        Ok(trainer)
+    }
+
+    pub fn update_trainee(&mut self, current_default : &NeuralNetSettings, new_cutoff : usize) -> Result<(), TinmanError> {
+        self.trainee.update_state(current_default)?;
         self.train_test_boundary = new_cutoff;
-        self.trainee.update_state(current_default);
+        self.rounds_completed = 0;
+        self.diverged = false;
+        if let Some(seed) = get_shuffle_seed(current_default) {
+            self.rand_index.reseed(seed);
+        }
+        Ok(())
+    }
+
+    // Restarts the row shuffling and the trainee's dropout from one seed.
+    pub fn reseed(&mut self, seed : u64) {
+        self.rand_index.reseed(derive_seed(seed, SHUFFLE_STREAM));
+        self.trainee.reseed(derive_seed(seed, DROPOUT_STREAM));
     }
+}
+
+const SHUFFLE_STREAM : u64 = 1;
+const DROPOUT_STREAM : u64 = 2;
+
+// the network draws its weights from the seed itself, shuffling gets its own stream
+fn get_shuffle_seed(settings : &NeuralNetSettings) -> Option<u64> {
+    settings.seed.map(|seed| derive_seed(seed, SHUFFLE_STREAM))
 }
\ No newline at end of file
@@ -3,10 +3,13 @@ use super::super::data_and_config::dataset::DataSet;
 use super::super::data_and_config::optimizer_parameters::OptimizerParameters;
 use super::super::classifier_parts::breeder::Breeder;
 use super::super::classifier_parts::nnet_trainer::NNetTrainer;
+use super::super::neural_network_parts::weight_init::init_layer_weights;
+use super::super::neural_network_parts::random::make_rng;
+use super::super::tinman_error::TinmanError;
 
 use std::thread::spawn;
 use std::sync::Arc;
-use rand::Rng;
+use rand::{Rng, StdRng};
 
 extern crate num_cpus;
 use num_cpus::get;
@@ -26,14 +29,29 @@ struct InternalSettings {
     breeder : Breeder,
     max_accuracy : f64,
     reset_weights : bool,
-    id_sequence : usize
+    id_sequence : usize,
+    // cpus_to_use is held to this, it is the machine's core count outside of tests
+    cores_available : usize,
+    // every random draw of the search comes from here, each candidate trainer gets a seed drawn from it
+    rng : StdRng
 }
 
 // Although this could be changed to a struct,
 // the user should see this as a mini program to call, not a object type.
 // Insert a request form, get a product as close to requested as possible.
-pub fn optimize_nnet_settings(order_form : &mut OptimizerParameters, data : &DataSet) {
-    let mut settings : InternalSettings = make_settings(order_form);
+pub fn optimize_nnet_settings(order_form : &mut OptimizerParameters, data : &DataSet) -> Result<(), TinmanError> {
+    optimize_nnet_settings_on_cores(order_form, data, get())
+}
+
+// As optimize_nnet_settings, with the number of cores to split the work over given
+// rather than read from the machine, so runs with different worker counts can be compared anywhere.
+pub fn optimize_nnet_settings_on_cores(
+    order_form : &mut OptimizerParameters,
+    data : &DataSet,
+    cores_available : usize
+) -> Result<(), TinmanError>
+{
+    let mut settings : InternalSettings = make_settings(order_form, cores_available)?;
     'outer: for i in 0 .. settings.optimizer_params.max_config_changing_epochs {
         settings.reset_weights = true;
         let mut prev_accuracy : f64 = 0.0;
@@ -42,8 +60,8 @@ pub fn optimize_nnet_settings(order_form : &mut OptimizerParameters, data : &Dat
             println!("config epoch: {}, training epoch: {}", i + 1, j + 1);
             alter_winners_per_round_if_final_round(i,j, &mut settings);
 
-            prev_accuracy = tune_hyper_parameters(&mut settings, data, prev_accuracy);
-            prev_accuracy = tune_weights(&mut settings, data, prev_accuracy);
+            prev_accuracy = tune_hyper_parameters(&mut settings, data, prev_accuracy)?;
+            prev_accuracy = tune_weights(&mut settings, data, prev_accuracy)?;
 
             if acceptable_accuracy_reached(&settings) {
                 break 'outer;
@@ -61,25 +79,26 @@ pub fn optimize_nnet_settings(order_form : &mut OptimizerParameters, data : &Dat
     }
     trim_nodes(&mut settings, data);
     collect_final_settings(&mut settings, order_form);
+    Ok(())
 }
 
-fn tune_hyper_parameters(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64 ) -> f64 {
+fn tune_hyper_parameters(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64 ) -> Result<f64, TinmanError> {
     settings.breeder.total_reset();
     println!("tune_hyper_parameters");
     while minimum_not_reached(settings) {
-        prev_accuracy = tune_hyper_parameters_w_sa(settings, data, prev_accuracy);
+        prev_accuracy = tune_hyper_parameters_w_sa(settings, data, prev_accuracy)?;
         if acceptable_accuracy_reached(&settings) {
-            return prev_accuracy;
+            return Ok(prev_accuracy);
         }
-        prev_accuracy = tune_hyper_parameters_w_ga(settings, data, prev_accuracy);
+        prev_accuracy = tune_hyper_parameters_w_ga(settings, data, prev_accuracy)?;
         if min_accuracy_inc_not_reached(prev_accuracy, settings) {
             println!("Accuracy not improving enough from GA, leaving loop");
             break;
         } else if acceptable_accuracy_reached(&settings) {
-            return prev_accuracy;
+            return Ok(prev_accuracy);
         }
     }
-    prev_accuracy
+    Ok(prev_accuracy)
 }
 
 fn print_off_info(settings : &mut InternalSettings) {
@@ -135,7 +154,7 @@ fn minimum_not_reached(settings : &mut InternalSettings) -> bool {
     !(settings.breeder.min_ga_mutations_reached() && settings.breeder.min_sa_mutations_reached())
 }
 
-fn tune_hyper_parameters_w_sa(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> f64 {
+fn tune_hyper_parameters_w_sa(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> Result<f64, TinmanError> {
     println!("tune_hyper_parameters_w_sa");
     while !settings.breeder.min_temp_reached() {
         for i in 0 .. settings.most_accurate_settings.len() {
@@ -148,13 +167,13 @@ fn tune_hyper_parameters_w_sa(settings : &mut InternalSettings, data : &DataSet,
         }
         settings.breeder.drop_temp();
     }
-    initiate_training_round(settings, data);
+    initiate_training_round(settings, data)?;
     prev_accuracy = settings.max_accuracy;
     settings.breeder.reset_temp_to_adjusted_max();
-    prev_accuracy
+    Ok(prev_accuracy)
 }
 
-fn tune_hyper_parameters_w_ga(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> f64 {
+fn tune_hyper_parameters_w_ga(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> Result<f64, TinmanError> {
     println!("tune_hyper_parameters_w_ga");
     while !settings.breeder.min_current_p1_fav_reached() {
         let most_accurate_length : usize = settings.most_accurate_settings.len();
@@ -173,7 +192,7 @@ fn tune_hyper_parameters_w_ga(settings : &mut InternalSettings, data : &DataSet,
             }
         }
         settings.breeder.drop_p1_favourability();
-        initiate_training_round(settings, data);
+        initiate_training_round(settings, data)?;
         if min_accuracy_inc_not_reached(prev_accuracy, settings) {
             println!("min accuracy inc. in h.p. ga not reached.");
             break;
@@ -181,51 +200,41 @@ fn tune_hyper_parameters_w_ga(settings : &mut InternalSettings, data : &DataSet,
             prev_accuracy = settings.max_accuracy;
         }
         if acceptable_accuracy_reached(&settings) {
-            return prev_accuracy;
+            return Ok(prev_accuracy);
         }
     }
     settings.breeder.reset_p1_fav_to_adjusted_max();
-    prev_accuracy
+    Ok(prev_accuracy)
 }
 
-fn initiate_training_round(settings : &mut InternalSettings, data : &DataSet) {
+fn initiate_training_round(settings : &mut InternalSettings, data : &DataSet) -> Result<(), TinmanError> {
     settings.breeder.inc_generation_count();
-    prepare_trainers(settings);
-    train_parallel(settings, data);
+    prepare_trainers(settings)?;
+    train_parallel(settings, data)?;
     collect_winners(settings);
-    reset_weights(settings);
+    reset_weights(settings)
 }
 
-fn get_recycled_trainer(settings : &mut InternalSettings) -> Box<NNetTrainer> {
+fn get_recycled_trainer(settings : &mut InternalSettings) -> Result<Box<NNetTrainer>, TinmanError> {
     // don't check candidate_trainers,
     // since it should always be empty when this function is called.
-    if settings.recycled_trainers.len() > 0 {
-        let trainer_opt : Option<Box<NNetTrainer>> = settings.recycled_trainers.pop();
-        match trainer_opt {
-            Some(trainer) => {
-                return trainer;
-            } None => {
-                panic!(no_recycled_trainer());
-            }
-        }
+    if let Some(trainer) = settings.recycled_trainers.pop() {
+        Ok(trainer)
     } else {
         let current_default : &NeuralNetSettings = &*settings.optimizer_params.current_candidate_configuration;
         let train_cutoff_idx : usize = settings.optimizer_params.test_train_cutoff_idx;
         let training_rounds_per_epoch : usize = settings.optimizer_params.train_rounds_per_epoch;
-        Box::new(NNetTrainer::new(current_default, train_cutoff_idx,training_rounds_per_epoch))
+        let mut trainer = NNetTrainer::new(current_default, train_cutoff_idx,training_rounds_per_epoch)?;
+        trainer.set_batch_size(settings.optimizer_params.batch_size.unwrap_or(1));
+        trainer.set_clip_value(settings.optimizer_params.clip_value);
+        trainer.set_clip_norm(settings.optimizer_params.clip_norm);
+        Ok(Box::new(trainer))
     }
 }
 
 fn get_child_settings(settings : &mut InternalSettings) -> Box<NeuralNetSettings> {
-    if settings.recycled_settings.len() > 0 {
-        let settings_opt : Option<Box<NeuralNetSettings>> = settings.recycled_settings.pop();
-        match settings_opt {
-            Some(child_settings) => {
-                return child_settings;
-            } None => {
-                panic!(no_recycled_settings());
-            }
-        }
+    if let Some(child_settings) = settings.recycled_settings.pop() {
+        child_settings
     } else {
         let current_settings : &NeuralNetSettings = &*settings.optimizer_params.current_candidate_configuration;
         let mut new_settings : NeuralNetSettings = current_settings.clone();
@@ -257,17 +266,31 @@ fn collect_final_settings(settings : &mut InternalSettings, order_form : &mut Op
     }
 }
 
-fn train_parallel(settings : &mut InternalSettings, data : &DataSet) {
-    process_parallel(settings, data, true);
+fn train_parallel(settings : &mut InternalSettings, data : &DataSet) -> Result<(), TinmanError> {
+    process_parallel(settings, data, true)
 }
 
-fn train_single(trainer : &mut Box<NNetTrainer>, shared_data : &Arc<&DataSet>) {
+fn train_single(trainer : &mut Box<NNetTrainer>, shared_data : &Arc<&DataSet>) -> Result<(), TinmanError> {
     let data : &DataSet = &shared_data;
-    trainer.train(data);
-    trainer.test(data);
+    let trained : Result<(), TinmanError> = trainer.train(data).and_then(|_| trainer.test(data));
+    fail_candidate_on_own_error(trainer, trained)
+}
+
+// An error that comes from one candidate's settings or weights only rules that candidate out,
+// it is marked failed and dropped with the diverged ones. Errors about the data or the
+// search settings would hit every candidate, so they still end the search.
+fn fail_candidate_on_own_error(trainer : &mut Box<NNetTrainer>, result : Result<(), TinmanError>) -> Result<(), TinmanError> {
+    match result {
+        Err(TinmanError::FailedPrediction) => {
+            println!("settings id: {} failed to make a prediction", trainer.get_trainee_id());
+            trainer.mark_failed();
+            Ok(())
+        } result => result
+    }
 }
 
 fn collect_winners(settings : &mut InternalSettings) {
+    drop_diverged_candidates(settings);
     copy_trainer_accuracy_to_settings(settings);
     expand_most_accurate_settings(settings);
     promote_candidates_to_most_accurate(settings);
@@ -275,6 +298,23 @@ fn collect_winners(settings : &mut InternalSettings) {
     set_top_score(settings);
 }
 
+// a diverged or failed trainer has no accuracy worth comparing, so its settings are recycled straight away
+fn drop_diverged_candidates(settings : &mut InternalSettings) {
+    for i in 0 .. settings.candidate_trainers.len() {
+        if !settings.candidate_trainers[i].has_diverged() {
+            continue;
+        }
+        let trainee_id : usize = settings.candidate_trainers[i].get_trainee_id();
+        let position : Option<usize> = settings.candidate_settings.iter().position(|candidate| candidate.config_id == trainee_id);
+        if let Some(j) = position {
+            println!("settings id: {} diverged, dropping it", trainee_id);
+            settings.recycled_settings.push(
+                settings.candidate_settings.remove(j)
+            );
+        }
+    }
+}
+
 fn copy_trainer_accuracy_to_settings(settings : &mut InternalSettings) {
     for i in 0 .. settings.candidate_trainers.len() {
         for j in 0 .. settings.candidate_settings.len() {
@@ -318,25 +358,11 @@ fn promote_candidates_to_most_accurate(settings : &mut InternalSettings) {
 }
 
 fn recycle_settings_and_trainers(settings : &mut InternalSettings) {
-    while settings.candidate_settings.len() > 0 {
-        let candidate_opt : Option<Box<NeuralNetSettings>> = settings.candidate_settings.pop();  
-        match candidate_opt {
-            Some(mut candidate) => {
-                settings.recycled_settings.push(candidate);
-            } None => {
-                panic!("No candidate found");
-            }
-        }
+    while let Some(candidate) = settings.candidate_settings.pop() {
+        settings.recycled_settings.push(candidate);
     }
-    while settings.candidate_trainers.len() > 0 {
-        let trainer_opt : Option<Box<NNetTrainer>> = settings.candidate_trainers.pop();
-        match trainer_opt {
-            Some(trainer) => {
-                settings.recycled_trainers.push(trainer);
-            } None => {
-                panic!("Not trainer found!");
-            }
-        }
+    while let Some(trainer) = settings.candidate_trainers.pop() {
+        settings.recycled_trainers.push(trainer);
     }
 }
 
@@ -358,17 +384,18 @@ fn set_top_score(settings : &mut InternalSettings) {
 }
 
 // lines up trainers for training with the most up to date settings, including weights
-fn prepare_trainers(settings : &mut InternalSettings) {
+fn prepare_trainers(settings : &mut InternalSettings) -> Result<(), TinmanError> {
     for i in 0 .. settings.candidate_settings.len() {
-        let mut recycled_trainer : NNetTrainer = *get_recycled_trainer(settings);
+        let mut recycled_trainer : NNetTrainer = *get_recycled_trainer(settings)?;
         recycled_trainer.update_trainee(
             &settings.candidate_settings[i],
             settings.optimizer_params.test_train_cutoff_idx
-        );
+        )?;
         settings.candidate_trainers.push(
             Box::new(recycled_trainer)
         );
     }
+    Ok(())
 }
 
 fn alter_winners_per_round_if_final_round(i : usize, j : usize, settings : &mut InternalSettings) {
@@ -388,66 +415,68 @@ fn acceptable_accuracy_reached(settings : &InternalSettings) -> bool {
 
 
 
-fn reset_weights(settings : &mut InternalSettings) {
+fn reset_weights(settings : &mut InternalSettings) -> Result<(), TinmanError> {
     if !settings.reset_weights {
-        return;
+        return Ok(());
     }
-    let mut rng = rand::thread_rng();
     for i in 0 .. settings.recycled_settings.len() {
+        let mut input_size : usize = settings.recycled_settings[i].input_size;
         for j in 0 .. settings.recycled_settings[i].layers.len() {
-            let low = settings.recycled_settings[i].layers[j].weight_range[0];
-            let high = settings.recycled_settings[i].layers[j].weight_range[1];
+            let new_weights : Vec<Vec<f64>> = init_layer_weights(
+                &settings.recycled_settings[i].layers[j],
+                input_size,
+                j,
+                &mut settings.rng
+            )?;
             match &mut settings.recycled_settings[i].layers[j].layer_weights {
                 Some(weight_vectors) => {
-                    for l in 0 .. weight_vectors.len() {
-                        for m in 0 .. weight_vectors[l]. len() {
-                            weight_vectors[l][m] = rng.gen_range(low, high);
-                        }
-                    }
+                    *weight_vectors = new_weights;
                 } None => {
-                    panic!("no weights to update");
+                    return Err(TinmanError::MissingWeights { layer : j });
                 }
             }
+            input_size = settings.recycled_settings[i].layers[j].output_units;
         }
     }
     println!("Done resetting weights.");
+    Ok(())
 }
 
 
-fn tune_weights(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> f64 {
+fn tune_weights(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> Result<f64, TinmanError> {
     settings.breeder.total_reset();
     println!("tune_weights");
     //settings.reset_weights = false;
     while minimum_not_reached(settings) {
-        prev_accuracy = tune_weights_w_sa(settings, data, prev_accuracy);
-        prev_accuracy = tune_weights_w_ga(settings, data, prev_accuracy);
+        prev_accuracy = tune_weights_w_sa(settings, data, prev_accuracy)?;
+        prev_accuracy = tune_weights_w_ga(settings, data, prev_accuracy)?;
         if min_accuracy_inc_not_reached(prev_accuracy, settings) {
             println!("Didn't reach increase in accuracy.");
             break;
         }
     }
-    prev_accuracy
+    Ok(prev_accuracy)
 }
 
-fn tune_weights_w_sa(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> f64 {
+fn tune_weights_w_sa(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> Result<f64, TinmanError> {
     while !settings.breeder.min_temp_reached() {
         for i in 0 .. settings.most_accurate_settings.len() {
             let mut child : NeuralNetSettings = *get_child_settings(settings);
             settings.breeder.child_w_sa_weights(
                 &mut child, 
                 &mut settings.most_accurate_settings[i]
-            );
+            )?;
             settings.candidate_settings.push(Box::new(child));
         }
         settings.breeder.drop_temp();
     }
-    initiate_searching_round(settings, data);
+    initiate_searching_round(settings, data)?;
     prev_accuracy = settings.max_accuracy;
     settings.breeder.reset_temp_to_adjusted_max();
-    prev_accuracy
+    Ok(prev_accuracy)
 }
 
-fn tune_weights_w_ga(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> f64 {
+fn tune_weights_w_ga(settings : &mut InternalSettings, data : &DataSet, mut prev_accuracy : f64) -> Result<f64, TinmanError> {
     while !settings.breeder.min_current_p1_fav_reached() {
         let most_accurate_length : usize = settings.most_accurate_settings.len();
         for i in 0 .. most_accurate_length {
@@ -460,19 +489,19 @@ fn tune_weights_w_ga(settings : &mut InternalSettings, data : &DataSet, mut prev
                     &mut child,
                     &settings.most_accurate_settings[i],
                     &settings.most_accurate_settings[j]
-                );
+                )?;
                 settings.candidate_settings.push(Box::new(child));
                 child = *get_child_settings(settings);
                 settings.breeder.child_w_ga_weights_swap(
                     &mut child,
                     &settings.most_accurate_settings[i],
                     &settings.most_accurate_settings[j]
-                );
+                )?;
                 settings.candidate_settings.push(Box::new(child));
             }
         }
         settings.breeder.drop_p1_favourability();
-        initiate_searching_round(settings, data);
+        initiate_searching_round(settings, data)?;
         if min_accuracy_inc_not_reached(prev_accuracy, settings) {
             println!("Minimum accuracy inc. not reached in tune weights with ga. leaving loop");
             break;
@@ -481,21 +510,24 @@ fn tune_weights_w_ga(settings : &mut InternalSettings, data : &DataSet, mut prev
         }
     }
     settings.breeder.reset_p1_fav_to_adjusted_max();
-    prev_accuracy
+    Ok(prev_accuracy)
 }
 
-fn initiate_searching_round(settings : &mut InternalSettings, data : &DataSet) {
+fn initiate_searching_round(settings : &mut InternalSettings, data : &DataSet) -> Result<(), TinmanError> {
     settings.breeder.inc_generation_count();
     let cutoff : usize = settings.optimizer_params.test_train_cutoff_idx;
     settings.optimizer_params.test_train_cutoff_idx = 0;
-    prepare_trainers(settings);
-    search_parallel(settings, data);
+    let prepared = prepare_trainers(settings);
+    // the cutoff is put back even if a trainer could not be prepared
+    let searched = prepared.and_then(|_| search_parallel(settings, data));
     settings.optimizer_params.test_train_cutoff_idx = cutoff; 
+    searched?;
     collect_winners(settings);
+    Ok(())
 }
 
 fn create_workload(settings : &mut InternalSettings, workload : &mut Vec<Vec<Box<NNetTrainer>>>) {
-    let mut num_cpus : usize = get();
+    let mut num_cpus : usize = settings.cores_available;
     if settings.optimizer_params.cpus_to_use < num_cpus {
         num_cpus = settings.optimizer_params.cpus_to_use;
         if num_cpus < 2 {
@@ -507,94 +539,97 @@ fn create_workload(settings : &mut InternalSettings, workload : &mut Vec<Vec<Box
         workload.push(thread_task);
     }
     let mut i : usize = 0;
-    while settings.candidate_trainers.len() > 0 {
-        let trainer_opt = settings.candidate_trainers.pop();
-        match trainer_opt {
-            Some(trainer_box) => {
-                workload[i].push(trainer_box);
-            } None => {
-                panic!("Could not aqquire trainer.");
-            }
-        }
+    while let Some(trainer_box) = settings.candidate_trainers.pop() {
+        workload[i].push(trainer_box);
         i += 1;
         i %= workload.len();
     }
 }
 
-fn search_parallel(settings : &mut InternalSettings, data : &DataSet) {
-    process_parallel(settings, data, false);
+fn search_parallel(settings : &mut InternalSettings, data : &DataSet) -> Result<(), TinmanError> {
+    process_parallel(settings, data, false)
 }
 
-fn process_parallel(settings : &mut InternalSettings, data : &DataSet, tune_params : bool) {
+fn process_parallel(settings : &mut InternalSettings, data : &DataSet, tune_params : bool) -> Result<(), TinmanError> {
     let mut shared_data : Arc<&DataSet> = Arc::new(data);
     let mut t_handles = vec![];
     let mut workload : Vec<Vec<Box<NNetTrainer>>> = Vec::new();
+    // each candidate gets its own seed before the work is split up,
+    // so how many workers there are doesn't change what it trains on
+    if tune_params {
+        for i in 0 .. settings.candidate_trainers.len() {
+            let candidate_seed : u64 = settings.rng.gen::<u64>();
+            settings.candidate_trainers[i].reseed(candidate_seed);
+        }
+    }
     create_workload(settings, &mut workload);
 
     scope(
         |scope| {
-            while workload.len() > 0 {
+            while let Some(trainer_box_vec) = workload.pop() {
                 let copy_data = shared_data.clone();
-                let trainers_opt = workload.pop();
-                match trainers_opt {
-                    Some(trainer_box_vec) => {
-                        t_handles.push(
-                            scope.spawn(
-                                move || {
-                                    process_batch(
-                                        trainer_box_vec, 
-                                        copy_data,
-                                        tune_params
-                                    )
-                                }
+                t_handles.push(
+                    scope.spawn(
+                        move || {
+                            process_batch(
+                                trainer_box_vec, 
+                                copy_data,
+                                tune_params
                             )
-                        );
-                    } None => {
-                        panic!("Could not aqquire trainer.");
-                    }
-                }
+                        }
+                    )
+                );
             }
         }
     );
+    // every worker is joined before the first error is passed on
+    let mut first_error : Option<TinmanError> = None;
     for handler in t_handles {
-        let mut trainers : Vec<Box<NNetTrainer>> = handler.join();
-        while trainers.len() > 0 {
-            let mut trainer_opt : Option<Box<NNetTrainer>> = trainers.pop();
-            match trainer_opt {
-                Some(trainer) => {
-                    settings.candidate_trainers.push(trainer);
-                } None => {
-                    panic!("Could not find nnet trainer");
-                }
+        let mut trainers : Vec<Box<NNetTrainer>> = match handler.join() {
+            Ok(trainers) => trainers,
+            Err(error) => {
+                first_error = first_error.or(Some(error));
+                continue;
             }
+        };
+        while let Some(trainer) = trainers.pop() {
+            settings.candidate_trainers.push(trainer);
         }
     }
+    // the workers hand their trainers back in whatever order they were split up,
+    // winners are picked in a fixed order so ties go the same way every run
+    settings.candidate_trainers.sort_by_key(|trainer| trainer.get_trainee_id());
+    match first_error {
+        Some(error) => Err(error),
+        None => Ok(())
+    }
 }
 
 fn process_batch(
     mut trainer_box_vec : Vec<Box<NNetTrainer>>, 
     shared_data : Arc<&DataSet>, 
     tune_params : bool
-) -> Vec<Box<NNetTrainer>> 
+) -> Result<Vec<Box<NNetTrainer>>, TinmanError>
 {
     if tune_params {
         for i in 0 .. trainer_box_vec.len() {
-            train_single(&mut trainer_box_vec[i], &shared_data);
+            train_single(&mut trainer_box_vec[i], &shared_data)?;
         }
     } else {
         for i in 0 .. trainer_box_vec.len() {
-            search_single(&mut trainer_box_vec[i], &shared_data);
+            search_single(&mut trainer_box_vec[i], &shared_data)?;
         }
     }
-    trainer_box_vec
+    Ok(trainer_box_vec)
 }
 
-fn search_single(trainer_box : &mut Box<NNetTrainer>, shared_data : &Arc<&DataSet>) {
+fn search_single(trainer_box : &mut Box<NNetTrainer>, shared_data : &Arc<&DataSet>) -> Result<(), TinmanError> {
     let data : &DataSet = &shared_data;
-    trainer_box.test(data);
+    let tested : Result<(), TinmanError> = trainer_box.test(data);
+    fail_candidate_on_own_error(trainer_box, tested)
 }
 
-fn make_settings(order_form : &mut OptimizerParameters) -> InternalSettings {
+fn make_settings(order_form : &mut OptimizerParameters, cores_available : usize) -> Result<InternalSettings, TinmanError> {
     let temp_drop_amt : f64 = 0.99 / order_form.temperature_drops as f64;
     let heritability_bias_drop : f64 = 0.4 / order_form.heritability_bias_drops as f64;
     let mut settings : InternalSettings = InternalSettings {
@@ -607,41 +642,40 @@ fn make_settings(order_form : &mut OptimizerParameters) -> InternalSettings {
         breeder : Breeder::new(temp_drop_amt, heritability_bias_drop),
         max_accuracy : 0.0,
         reset_weights : true,
-        id_sequence : 2 // bc original settings is pushed onto vector below
+        id_sequence : 2, // bc original settings is pushed onto vector below
+        cores_available : cores_available.max(1),
+        rng : make_rng(order_form.seed)
     };
+    let breeder_seed : u64 = settings.rng.gen::<u64>();
+    settings.breeder.reseed(breeder_seed);
     settings.optimizer_params.current_candidate_configuration.config_id = 1;
-    validate_and_adjust_parameters(&mut settings);
+    validate_and_adjust_parameters(&mut settings)?;
     settings.most_accurate_settings.push(
         settings.optimizer_params.current_candidate_configuration.clone()
     );
-    settings
+    Ok(settings)
 }
 
-fn validate_and_adjust_parameters(settings : &mut InternalSettings) {
+fn validate_and_adjust_parameters(settings : &mut InternalSettings) -> Result<(), TinmanError> {
     // add weight vectors if they are missing
     // will need to complete code change to update neural network
-    let mut rng = rand::thread_rng();
     let mut input_size : usize = settings.optimizer_params.current_candidate_configuration.input_size;
     let mut layers : &mut Vec<LayerSettings> = &mut settings.optimizer_params.current_candidate_configuration.layers;
     for i in 0 .. layers.len() {
-        let low : f64 = layers[i].weight_range[0];
-        let high : f64 = layers[i].weight_range[1];
-        let mut new_layer_weights : Vec<Vec<f64>> = Vec::new();
-        for j in 0 .. layers[i].output_units {
-            new_layer_weights.push(vec![rng.gen_range(low, high); input_size]);
-        }
+        let new_layer_weights : Vec<Vec<f64>> = init_layer_weights(&layers[i], input_size, i, &mut settings.rng)?;
         layers[i].layer_weights = Some(new_layer_weights);
         input_size = layers[i].output_units;
     }
     if settings.optimizer_params.winners_per_round < 2 {
         settings.optimizer_params.winners_per_round = 2;
     }
-    let cores_available : usize = num_cpus::get();
+    let cores_available : usize = settings.cores_available;
     if settings.optimizer_params.cpus_to_use > cores_available {
         settings.optimizer_params.cpus_to_use = cores_available;
     } else if settings.optimizer_params.cpus_to_use < 1 {
         settings.optimizer_params.cpus_to_use = 1;
     }
+    Ok(())
 }
 
 fn alter_activation_fns_and_optimizers(settings : &mut InternalSettings) {
@@ -663,17 +697,7 @@ fn trim_nodes(settings : &mut InternalSettings, data : &DataSet) {
 
 }
 
-#[allow(dead_code)]
-fn no_recycled_settings() -> String {
-    "Optimize_nnet_settings failed in function get_child_settings".to_string()
-}
-
 #[allow(dead_code)]
 fn no_winners_to_add_to_candidate_list() -> String {
     "Optimize_nnet_settings failed in function add_prev_winners_to_candidates".to_string()
-}
-
-#[allow(dead_code)]
-fn no_recycled_trainer() -> String {
-    "No recycled trainer found".to_string()
 }
\ No newline at end of file
@@ -14,8 +14,15 @@ pub struct TableInfo {
     pub column_names : Option<Vec<String>>
 }
 
+// Classification rows use label, multi-label rows use labels,
+// regression rows use targets, one target per unit in the output layer.
 #[derive(Serialize, Deserialize)]
 pub struct Row {
+    #[serde(default)]
     pub label : usize,
     pub columns : Vec<f64>,
+    #[serde(default)]
+    pub labels : Vec<usize>,
+    #[serde(default)]
+    pub targets : Vec<f64>
 }
\ No newline at end of file
@@ -1,4 +1,6 @@
 pub mod dataset;
 pub mod neural_net_config_parts;
 pub mod test_config;
-pub mod optimizer_parameters;
\ No newline at end of file
+pub mod optimizer_parameters;
+pub mod model_file;
+pub mod validate;
\ No newline at end of file
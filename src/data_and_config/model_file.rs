@@ -0,0 +1,216 @@
+use serde_derive::{Serialize, Deserialize};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use super::neural_net_config_parts::NeuralNetSettings;
+use super::super::tinman_error::TinmanError;
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+pub const FORMAT_VERSION : u32 = 1;
+pub const NEURAL_NETWORK_KIND : &str = "neural_network";
+pub const NNET_TRAINER_KIND : &str = "nnet_trainer";
+
+const BINARY_MAGIC : [u8; 4] = *b"TNMB";
+
+// Every saved file carries the format version and what kind of object it holds,
+// so older files can be recognised (or rejected) when the layout changes.
+#[derive(Serialize, Deserialize)]
+struct ModelFile<T> {
+    format_version : u32,
+    kind : String,
+    body : T
+}
+
+pub fn save_json<T : Serialize, P : AsRef<Path>>(path : P, kind : &str, body : &T) -> Result<(), TinmanError> {
+    let file : ModelFile<&T> = ModelFile {
+        format_version : FORMAT_VERSION,
+        kind : kind.to_string(),
+        body
+    };
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, &file)?;
+    Ok(writer.flush()?)
+}
+
+pub fn load_json<T : DeserializeOwned, P : AsRef<Path>>(path : P, kind : &str) -> Result<T, TinmanError> {
+    let reader = BufReader::new(File::open(path)?);
+    let file : ModelFile<Value> = serde_json::from_reader(reader)?;
+    check_header(file.format_version, &file.kind, kind)?;
+    Ok(serde_json::from_value(file.body)?)
+}
+
+// Binary layout, all numbers little-endian:
+//   magic "TNMB" | format version u32 | header length u64 | JSON header
+//   then one block per layer: has weights u8 | rows u64 | columns u64 | rows * columns f64
+// The JSON header is the same envelope as the JSON format, with the layer weights taken out.
+pub fn save_binary<T, P, F>(path : P, kind : &str, mut body : T, settings_of : F) -> Result<(), TinmanError>
+where
+    T : Serialize,
+    P : AsRef<Path>,
+    F : Fn(&mut T) -> &mut NeuralNetSettings
+{
+    let mut weights : Vec<Option<Vec<Vec<f64>>>> = Vec::new();
+    for (layer_index, layer) in settings_of(&mut body).layers.iter_mut().enumerate() {
+        let layer_weights : Option<Vec<Vec<f64>>> = layer.layer_weights.take();
+        if let Some(weight_vectors) = &layer_weights {
+            if weight_vectors.iter().any(|weight_vector| weight_vector.len() != weight_vectors[0].len()) {
+                return Err(TinmanError::RaggedWeightVectors { layer : layer_index });
+            }
+        }
+        weights.push(layer_weights);
+    }
+    let header : Vec<u8> = serde_json::to_vec(&ModelFile {
+        format_version : FORMAT_VERSION,
+        kind : kind.to_string(),
+        body
+    })?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&BINARY_MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&(header.len() as u64).to_le_bytes())?;
+    writer.write_all(&header)?;
+    for layer_weights in &weights {
+        write_weight_block(&mut writer, layer_weights)?;
+    }
+    Ok(writer.flush()?)
+}
+
+pub fn load_binary<T, P, F>(path : P, kind : &str, settings_of : F) -> Result<T, TinmanError>
+where
+    T : DeserializeOwned,
+    P : AsRef<Path>,
+    F : Fn(&mut T) -> &mut NeuralNetSettings
+{
+    let file : File = File::open(path)?;
+    let file_len : u64 = file.metadata()?.len();
+    let mut reader = BoundedReader { reader : BufReader::new(file), remaining : file_len };
+    let mut magic : [u8; 4] = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != BINARY_MAGIC {
+        return Err(TinmanError::NotABinaryModelFile);
+    }
+    let format_version : u32 = read_u32(&mut reader)?;
+    check_version(format_version)?;
+    let header_len : u64 = read_u64(&mut reader)?;
+    let header_len : usize = reader.check_length(header_len, 1)?;
+    let mut header : Vec<u8> = vec![0; header_len];
+    reader.read_exact(&mut header)?;
+    let file : ModelFile<Value> = serde_json::from_slice(&header)?;
+    check_header(file.format_version, &file.kind, kind)?;
+
+    let mut body : T = serde_json::from_value(file.body)?;
+    let settings : &mut NeuralNetSettings = settings_of(&mut body);
+    for layer in settings.layers.iter_mut() {
+        layer.layer_weights = read_weight_block(&mut reader)?;
+    }
+    Ok(body)
+}
+
+fn write_weight_block<W : Write>(writer : &mut W, weights : &Option<Vec<Vec<f64>>>) -> io::Result<()> {
+    match weights {
+        Some(weight_vectors) => {
+            let columns : usize = if weight_vectors.is_empty() { 0 } else { weight_vectors[0].len() };
+            writer.write_all(&[1])?;
+            writer.write_all(&(weight_vectors.len() as u64).to_le_bytes())?;
+            writer.write_all(&(columns as u64).to_le_bytes())?;
+            for weight_vector in weight_vectors {
+                for weight in weight_vector {
+                    writer.write_all(&weight.to_le_bytes())?;
+                }
+            }
+        } None => {
+            writer.write_all(&[0])?;
+        }
+    }
+    Ok(())
+}
+
+fn read_weight_block<R : Read>(reader : &mut BoundedReader<R>) -> Result<Option<Vec<Vec<f64>>>, TinmanError> {
+    let mut has_weights : [u8; 1] = [0];
+    reader.read_exact(&mut has_weights)?;
+    if has_weights[0] == 0 {
+        return Ok(None);
+    }
+    let rows : u64 = read_u64(reader)?;
+    let columns : u64 = read_u64(reader)?;
+    // the sizes come from the file, so they are held to what is left of it before anything is allocated,
+    // rows of no weights take no room, so a row count without columns can't be checked and is rejected
+    if rows > 0 && columns == 0 {
+        return Err(TinmanError::CorruptModelFile);
+    }
+    let weight_count : u64 = rows.checked_mul(columns).ok_or(TinmanError::CorruptModelFile)?;
+    reader.check_length(weight_count, 8)?;
+    let rows : usize = rows as usize;
+    let columns : usize = columns as usize;
+    let mut weight_vectors : Vec<Vec<f64>> = Vec::with_capacity(rows);
+    for _ in 0 .. rows {
+        let mut weight_vector : Vec<f64> = Vec::with_capacity(columns);
+        for _ in 0 .. columns {
+            weight_vector.push(read_f64(reader)?);
+        }
+        weight_vectors.push(weight_vector);
+    }
+    Ok(Some(weight_vectors))
+}
+
+// Reads a file while counting the bytes left in it, so lengths read from
+// the file can be checked before anything is allocated for them.
+struct BoundedReader<R : Read> {
+    reader : R,
+    remaining : u64
+}
+
+impl<R : Read> BoundedReader<R> {
+    // count items of item_size bytes each must fit in the rest of the file
+    fn check_length(&self, count : u64, item_size : u64) -> Result<usize, TinmanError> {
+        match count.checked_mul(item_size) {
+            Some(bytes) if bytes <= self.remaining => Ok(count as usize),
+            _ => Err(TinmanError::CorruptModelFile)
+        }
+    }
+}
+
+impl<R : Read> Read for BoundedReader<R> {
+    fn read(&mut self, buffer : &mut [u8]) -> io::Result<usize> {
+        let read : usize = self.reader.read(buffer)?;
+        self.remaining = self.remaining.saturating_sub(read as u64);
+        Ok(read)
+    }
+}
+
+fn read_u32<R : Read>(reader : &mut R) -> io::Result<u32> {
+    let mut bytes : [u8; 4] = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R : Read>(reader : &mut R) -> io::Result<u64> {
+    let mut bytes : [u8; 8] = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64<R : Read>(reader : &mut R) -> io::Result<f64> {
+    let mut bytes : [u8; 8] = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+fn check_header(format_version : u32, found_kind : &str, expected_kind : &str) -> Result<(), TinmanError> {
+    check_version(format_version)?;
+    if found_kind != expected_kind {
+        return Err(TinmanError::WrongModelKind { expected : expected_kind.to_string(), found : found_kind.to_string() });
+    }
+    Ok(())
+}
+
+fn check_version(format_version : u32) -> Result<(), TinmanError> {
+    if format_version > FORMAT_VERSION {
+        return Err(TinmanError::UnsupportedFormatVersion { version : format_version, supported : FORMAT_VERSION });
+    }
+    Ok(())
+}
@@ -1,14 +1,66 @@
 use serde_derive::{Serialize, Deserialize};
 
+pub const CLASSIFICATION : &str = "classification";
+pub const REGRESSION : &str = "regression";
+pub const MULTI_LABEL : &str = "multi_label";
+pub const DENSE : &str = "dense";
+pub const BATCH_NORM : &str = "batch_norm";
+pub const F64 : &str = "f64";
+pub const F32 : &str = "f32";
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct LayerSettings {
     pub activation_function : String,
+    pub activation_parameters : Option<Vec<f64>>,
     pub weight_range : [f64; 2],
+    pub init : Option<String>,
     pub layer_weights : Option<Vec<Vec<f64>>>,
+    pub layer_biases : Option<Vec<f64>>,
     pub output_units : usize,
     pub bias : f64,
     pub learning_rate : f64,
-    pub momentum : f64
+    pub momentum : f64,
+    pub optimizer : Option<String>,
+    pub beta1 : Option<f64>,
+    pub beta2 : Option<f64>,
+    pub epsilon : Option<f64>,
+    pub learning_rate_schedule : Option<ScheduleSettings>,
+    // regularization is off when None, the breeder only tunes values that are set
+    pub l1 : Option<f64>,
+    pub l2 : Option<f64>,
+    pub max_norm : Option<f64>,
+    pub dropout_rate : Option<f64>,
+    pub layer_type : Option<String>,
+    pub running_mean : Option<Vec<f64>>,
+    pub running_variance : Option<Vec<f64>>
+}
+
+impl LayerSettings {
+    // settings without a layer type are dense layers
+    pub fn get_layer_type(&self) -> &str {
+        match &self.layer_type {
+            Some(layer_type) => layer_type,
+            None => DENSE
+        }
+    }
+
+    // A batch norm layer has one node per unit of the layer before it,
+    // each with a single weight (gamma) and its bias (beta).
+    pub fn is_batch_norm(&self) -> bool {
+        self.get_layer_type() == BATCH_NORM
+    }
+}
+
+// Which learning rate schedule a layer trains with, and its parameters.
+// Parameters a schedule doesn't use are ignored, missing ones get defaults.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScheduleSettings {
+    pub schedule : String,
+    pub decay_rate : Option<f64>,
+    pub step_rounds : Option<usize>,
+    pub min_learning_rate : Option<f64>,
+    pub patience : Option<usize>,
+    pub cycle_multiplier : Option<f64>
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -17,5 +69,29 @@ pub struct NeuralNetSettings {
     pub config_id : usize,
     pub accuracy : f64,
     pub input_size : usize,
+    pub loss : Option<String>,
+    pub task : Option<String>,
+    pub label_threshold : Option<f64>,
+    pub seed : Option<u64>,
+    pub precision : Option<String>,
     pub layers : Vec<LayerSettings>
-}
\ No newline at end of file
+}
+
+impl NeuralNetSettings {
+    // settings without a task are classifiers
+    pub fn get_task(&self) -> &str {
+        match &self.task {
+            Some(task) => task,
+            None => CLASSIFICATION
+        }
+    }
+
+    // Float type the layers keep their weights and state in,
+    // settings without a precision are f64.
+    pub fn get_precision(&self) -> &str {
+        match &self.precision {
+            Some(precision) => precision,
+            None => F64
+        }
+    }
+}
@@ -8,11 +8,15 @@ pub struct OptimizerParameters {
     pub current_candidate_configuration : Box<NeuralNetSettings>,
     pub test_train_cutoff_idx : usize,
     pub train_rounds_per_epoch : usize,
+    pub batch_size : Option<usize>,
+    pub clip_value : Option<f64>,
+    pub clip_norm : Option<f64>,
     pub max_train_epochs : usize,
     pub max_config_changing_epochs : usize,
     pub final_number_of_nnet_settings : usize,
     pub winners_per_round : usize,
     pub tuned_settings : Vec<Box<NeuralNetSettings>>,
     pub min_acceptable_accuracy : f64,
-    pub cpus_to_use : usize
+    pub cpus_to_use : usize,
+    pub seed : Option<u64>
 }
\ No newline at end of file
@@ -0,0 +1,173 @@
+use super::neural_net_config_parts::{NeuralNetSettings, LayerSettings, REGRESSION, MULTI_LABEL};
+use super::dataset::{DataSet, Row};
+use super::super::neural_network_parts::optimizer::Optimizer;
+use super::super::neural_network_parts::learning_rate_schedule::LearningRateSchedule;
+use super::super::neural_network_parts::layer_weights::{
+    check_layer_weights,
+    get_layer_biases,
+    get_weights_per_node
+};
+use super::super::neural_network_parts::weight_init::init_code_by_name;
+use super::super::neural_network_parts::layer::{get_running_stats, get_activation_parameters};
+use super::super::neural_network_parts::layer_stack::check_precision;
+use super::super::neural_network_parts::neural_network::{check_task, check_softmax_layers, get_activation, make_loss};
+use super::super::tinman_error::TinmanError;
+
+// Checks settings and data against each other before any training is done,
+// and returns every problem found instead of stopping at the first.
+pub fn validate(nnet_settings : &NeuralNetSettings, dataset : &DataSet) -> Result<(), Vec<TinmanError>> {
+    let mut errors : Vec<TinmanError> = Vec::new();
+    validate_settings(nnet_settings, &mut errors);
+    for (i, row) in dataset.data.iter().enumerate() {
+        validate_row(nnet_settings, dataset, row, i, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_settings(nnet_settings : &NeuralNetSettings, errors : &mut Vec<TinmanError>) {
+    if let Err(error) = check_task(nnet_settings) {
+        errors.push(error);
+    }
+    if let Err(error) = check_precision(nnet_settings) {
+        errors.push(error);
+    }
+    if nnet_settings.layers.is_empty() {
+        errors.push(TinmanError::NoLayers);
+        return;
+    }
+    if let Err(error) = make_loss(nnet_settings) {
+        errors.push(error);
+    }
+    if let Err(error) = check_softmax_layers(nnet_settings) {
+        errors.push(error);
+    }
+    let mut input_size : usize = nnet_settings.input_size;
+    for (i, layer_settings) in nnet_settings.layers.iter().enumerate() {
+        validate_layer(layer_settings, input_size, i, errors);
+        input_size = layer_settings.output_units;
+    }
+}
+
+fn validate_layer(
+    layer_settings : &LayerSettings,
+    input_size : usize,
+    layer : usize,
+    errors : &mut Vec<TinmanError>
+)
+{
+    if layer_settings.output_units == 0 {
+        errors.push(TinmanError::EmptyLayer { layer });
+    }
+    let low : f64 = layer_settings.weight_range[0];
+    let high : f64 = layer_settings.weight_range[1];
+    if low > high || low.is_nan() || high.is_nan() {
+        errors.push(TinmanError::InvalidWeightRange { layer, low, high });
+    }
+    // a rate of 1 drops every unit, and the kept ones are scaled by 1 / (1 - rate)
+    if let Some(rate) = layer_settings.dropout_rate {
+        if !(0.0 .. 1.0).contains(&rate) {
+            errors.push(TinmanError::InvalidDropoutRate { layer, rate });
+        }
+    }
+    let mut results : Vec<Result<(), TinmanError>> = vec![
+        get_activation(layer_settings, layer)
+            .and_then(|activation| get_activation_parameters(layer_settings, &*activation, layer))
+            .map(|_| ()),
+        Optimizer::new(layer_settings, layer).map(|_| ()),
+        LearningRateSchedule::new(layer_settings, layer).map(|_| ()),
+        get_layer_biases(layer_settings, layer).map(|_| ()),
+        get_running_stats(layer_settings, layer).map(|_| ())
+    ];
+    if let Some(init_name) = &layer_settings.init {
+        if init_code_by_name(init_name).is_none() {
+            results.push(Err(TinmanError::UnknownInit { layer, name : init_name.to_string() }));
+        }
+    }
+    // the weight shapes can only be checked once the layer type and size are known good
+    match get_weights_per_node(layer_settings, input_size, layer) {
+        Ok(_) => {
+            if let Some(weight_vectors) = &layer_settings.layer_weights {
+                results.push(check_layer_weights(weight_vectors, layer_settings, input_size, layer));
+            }
+        } Err(error) => {
+            results.push(Err(error));
+        }
+    }
+    for result in results {
+        if let Err(error) = result {
+            errors.push(error);
+        }
+    }
+}
+
+fn validate_row(
+    nnet_settings : &NeuralNetSettings,
+    dataset : &DataSet,
+    row : &Row,
+    index : usize,
+    errors : &mut Vec<TinmanError>
+)
+{
+    if row.columns.len() != nnet_settings.input_size {
+        errors.push(TinmanError::InputSizeMismatch {
+            row : Some(index),
+            expected : nnet_settings.input_size,
+            actual : row.columns.len()
+        });
+    }
+    for (column, value) in row.columns.iter().enumerate() {
+        if !value.is_finite() {
+            errors.push(TinmanError::NonFiniteFeature { row : index, column });
+        }
+    }
+    let output_units : usize = match nnet_settings.layers.last() {
+        Some(output_layer) => output_layer.output_units,
+        None => return
+    };
+    match nnet_settings.get_task() {
+        REGRESSION => {
+            if row.targets.len() != output_units {
+                errors.push(TinmanError::TargetSizeMismatch {
+                    row : Some(index),
+                    expected : output_units,
+                    actual : row.targets.len()
+                });
+            }
+            for (target, value) in row.targets.iter().enumerate() {
+                if !value.is_finite() {
+                    errors.push(TinmanError::NonFiniteTarget { row : index, target });
+                }
+            }
+        }
+        MULTI_LABEL => {
+            for label in &row.labels {
+                validate_label(*label, output_units, dataset, index, errors);
+            }
+        }
+        _ => {
+            validate_label(row.label, output_units, dataset, index, errors);
+        }
+    }
+}
+
+// An empty result map means the classes are not named, so only the output units bound the label.
+fn validate_label(
+    label : usize,
+    output_units : usize,
+    dataset : &DataSet,
+    index : usize,
+    errors : &mut Vec<TinmanError>
+)
+{
+    if label >= output_units {
+        errors.push(TinmanError::LabelOutOfRange { row : Some(index), label, output_units });
+    }
+    let classes : usize = dataset.result_map.len();
+    if classes > 0 && label >= classes {
+        errors.push(TinmanError::LabelBeyondResultMap { row : index, label, classes });
+    }
+}
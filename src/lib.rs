@@ -1,12 +1,16 @@
 pub mod neural_network_parts;
 pub mod data_and_config;
 pub mod classifier_parts;
+pub mod tinman_error;
 
 pub use neural_network_parts::neural_network::NeuralNetwork;
+pub use neural_network_parts::activator::{Activation, register_activation};
 pub use data_and_config::neural_net_config_parts::NeuralNetSettings;
 pub use data_and_config::dataset::DataSet;
+pub use data_and_config::validate::validate;
 pub use data_and_config::test_config::{TestConfig, Test};
 pub use classifier_parts::nnet_trainer::NNetTrainer;
 pub use classifier_parts::breeder::Breeder;
+pub use tinman_error::TinmanError;
 //pub use classifier_parts::classifier::Classifier;
 pub mod prelude;
\ No newline at end of file
@@ -1,111 +1,270 @@
-
+use super::super::tinman_error::TinmanError;
 use std::f64::consts::{E, PI};
+use std::sync::{Arc, RwLock};
 
 
-use super::nnet_errors::{
-    set_act_fn_code_out_of_bounds, 
-    fn_name_not_found
-};
-
 const LAMBDA : f64 = 1.6732632423543772848170429916717;
 const ALPHA : f64 = 1.0507009873554804934193349852946;
 
-pub struct Activator {
-    fn_names : [String; 12],
-    activation_fn_code : usize,
-    activation_fns : [fn(f64) -> f64; 12],
-    activation_prime_fns : [fn(f64) -> f64; 12]
-}
-
-impl Activator {
-    pub fn new () -> Activator {
-        Activator {
-            activation_fn_code : 0,
-            fn_names : [
-                "default".to_string(),
-                "sigmoid".to_string(),
-                "binary_step".to_string(),
-                "tanh".to_string(),
-                "sqnl".to_string(),
-                "arctan".to_string(),
-                "lrelu".to_string(),
-                "elu".to_string(),
-                "selu".to_string(),
-                "gelu".to_string(),
-                "softplus".to_string(),
-                "swish".to_string()
-            ],
-            activation_fns : [
-                default,
-                sigmoid,
-                binary_step,
-                tanh,
-                sqnl,
-                arctan,
-                lrelu,
-                elu,
-                selu,
-                gelu,
-                softplus,
-                swish
-            ],
-            activation_prime_fns : [
-                default_prime,
-                sigmoid_prime,
-                binary_step_prime,
-                tanh_prime,
-                sqnl_prime,
-                arctan_prime,
-                lrelu_prime,
-                elu_prime,
-                selu_prime,
-                gelu_prime,
-                softplus_prime,
-                swish
-            ]
+// Softmax needs the whole layer, so its activation only passes the raw sums through
+// and the Layer normalizes them afterwards.
+pub const SOFTMAX : &str = "softmax";
+pub const SIGMOID : &str = "sigmoid";
+pub const PRELU : &str = "prelu";
+pub const PELU : &str = "pelu";
+pub const PSWISH : &str = "pswish";
+
+// An activation function and its derivative, found by the name settings give it.
+// Parametric activations also have parameters each layer learns, the current values
+// are passed to every call. Fixed activations ignore them.
+pub trait Activation : Send + Sync {
+    fn get_name(&self) -> &str;
+    fn activate(&self, x : f64, parameters : &[f64]) -> f64;
+    fn derivative(&self, x : f64, parameters : &[f64]) -> f64;
+
+    // values the parameters start from when the settings don't give any
+    fn get_default_parameters(&self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    // how the output moves with each parameter, one slot per parameter
+    fn parameter_derivatives(&self, _x : f64, _parameters : &[f64], _derivatives : &mut [f64]) {}
+}
+
+// Activations added by users of the crate, looked up after the built in ones.
+static CUSTOM_ACTIVATIONS : RwLock<Vec<Arc<dyn Activation>>> = RwLock::new(Vec::new());
+
+// Makes an activation available to every network by its name. Registering a name
+// again replaces the earlier activation, the built in names can't be taken.
+pub fn register_activation(activation : Arc<dyn Activation>) -> Result<(), TinmanError> {
+    let name : String = activation.get_name().to_string();
+    if get_built_in_activation(&name).is_some() {
+        return Err(TinmanError::ActivationNameTaken { name });
+    }
+    let mut custom_activations = CUSTOM_ACTIVATIONS.write().unwrap_or_else(|error| error.into_inner());
+    custom_activations.retain(|custom| custom.get_name() != name);
+    custom_activations.push(activation);
+    Ok(())
+}
+
+pub fn get_activation_by_name(name : &str) -> Option<Arc<dyn Activation>> {
+    if let Some(activation) = get_built_in_activation(name) {
+        return Some(activation);
+    }
+    let custom_activations = CUSTOM_ACTIVATIONS.read().unwrap_or_else(|error| error.into_inner());
+    custom_activations.iter().find(|custom| custom.get_name() == name).cloned()
+}
+
+fn get_built_in_activation(name : &str) -> Option<Arc<dyn Activation>> {
+    if let Some(fn_activation) = FN_ACTIVATIONS.iter().find(|fn_activation| fn_activation.name == name) {
+        return Some(Arc::new(fn_activation.clone()));
+    }
+    match name {
+        PRELU => Some(Arc::new(Prelu)),
+        PELU => Some(Arc::new(Pelu)),
+        PSWISH => Some(Arc::new(Pswish)),
+        _ => None
+    }
+}
+
+// every name settings can use, built in ones first
+pub fn get_activation_names() -> Vec<String> {
+    let mut names : Vec<String> = FN_ACTIVATIONS.iter().map(|fn_activation| fn_activation.name.to_string()).collect();
+    for name in &[PRELU, PELU, PSWISH] {
+        names.push(name.to_string());
+    }
+    let custom_activations = CUSTOM_ACTIVATIONS.read().unwrap_or_else(|error| error.into_inner());
+    for custom in custom_activations.iter() {
+        names.push(custom.get_name().to_string());
+    }
+    names
+}
+
+const CHECK_STEP : f64 = 1e-5;
+const CHECK_POINTS : usize = 160;
+const EXTREME_INPUTS : [f64; 6] = [-1000.0, -100.0, -40.0, 40.0, 100.0, 1000.0];
+
+// Compares an activation's derivative, and the derivatives for its parameters, with
+// central differences at its default parameters and returns the worst error found.
+// Inputs run from -7.95 to 7.95 in steps of 0.1, which keeps clear of the kinks
+// at 0 and +-2 the built in activations have. Errors are relative once the derivative
+// is above 1 and absolute below it. A value or derivative that isn't finite,
+// including at large inputs, is an infinite error.
+pub fn check_derivatives(activation : &dyn Activation) -> f64 {
+    let parameters : Vec<f64> = activation.get_default_parameters();
+    let mut parameter_derivatives : Vec<f64> = vec![0.0; parameters.len()];
+    let mut worst_error : f64 = 0.0;
+    for x in &EXTREME_INPUTS {
+        let value : f64 = activation.activate(*x, &parameters);
+        let derivative : f64 = activation.derivative(*x, &parameters);
+        if !value.is_finite() || !derivative.is_finite() {
+            return f64::INFINITY;
+        }
+    }
+    for i in 0 .. CHECK_POINTS {
+        let x : f64 = i as f64 * 0.1 - 7.95;
+        let numerical : f64 = (activation.activate(x + CHECK_STEP, &parameters)
+            - activation.activate(x - CHECK_STEP, &parameters)) / (2.0 * CHECK_STEP);
+        worst_error = worst_error.max(derivative_error(activation.derivative(x, &parameters), numerical));
+        activation.parameter_derivatives(x, &parameters, &mut parameter_derivatives);
+        for p in 0 .. parameters.len() {
+            let mut shifted : Vec<f64> = parameters.clone();
+            shifted[p] = parameters[p] + CHECK_STEP;
+            let above : f64 = activation.activate(x, &shifted);
+            shifted[p] = parameters[p] - CHECK_STEP;
+            let below : f64 = activation.activate(x, &shifted);
+            worst_error = worst_error.max(derivative_error(parameter_derivatives[p], (above - below) / (2.0 * CHECK_STEP)));
         }
     }
+    worst_error
+}
+
+// relative for values above 1 and absolute below, so rounding in small differences isn't magnified
+pub fn derivative_error(analytical : f64, numerical : f64) -> f64 {
+    if !analytical.is_finite() || !numerical.is_finite() {
+        return f64::INFINITY;
+    }
+    (analytical - numerical).abs() / analytical.abs().max(numerical.abs()).max(1.0)
+}
 
-    pub fn activate (&mut self, x : f64) -> f64 {
-        (self.activation_fns)[self.activation_fn_code](x)
+// A fixed activation made of a pair of plain functions.
+#[derive(Clone)]
+struct FnActivation {
+    name : &'static str,
+    activation_fn : fn(f64) -> f64,
+    activation_prime_fn : fn(f64) -> f64
+}
+
+impl Activation for FnActivation {
+    fn get_name(&self) -> &str {
+        self.name
     }
- 
-    pub fn activate_prime (&mut self, x : f64) -> f64 {
-        (self.activation_prime_fns)[self.activation_fn_code](x)
+
+    fn activate(&self, x : f64, _parameters : &[f64]) -> f64 {
+        (self.activation_fn)(x)
+    }
+
+    fn derivative(&self, x : f64, _parameters : &[f64]) -> f64 {
+        (self.activation_prime_fn)(x)
+    }
+}
+
+static FN_ACTIVATIONS : [FnActivation; 14] = [
+    FnActivation { name : "default", activation_fn : default, activation_prime_fn : default_prime },
+    FnActivation { name : SIGMOID, activation_fn : sigmoid, activation_prime_fn : sigmoid_prime },
+    FnActivation { name : "binary_step", activation_fn : binary_step, activation_prime_fn : binary_step_prime },
+    FnActivation { name : "tanh", activation_fn : tanh, activation_prime_fn : tanh_prime },
+    FnActivation { name : "sqnl", activation_fn : sqnl, activation_prime_fn : sqnl_prime },
+    FnActivation { name : "arctan", activation_fn : arctan, activation_prime_fn : arctan_prime },
+    FnActivation { name : "lrelu", activation_fn : lrelu, activation_prime_fn : lrelu_prime },
+    FnActivation { name : "elu", activation_fn : elu, activation_prime_fn : elu_prime },
+    FnActivation { name : "selu", activation_fn : selu, activation_prime_fn : selu_prime },
+    FnActivation { name : "gelu", activation_fn : gelu, activation_prime_fn : gelu_prime },
+    FnActivation { name : "softplus", activation_fn : softplus, activation_prime_fn : softplus_prime },
+    FnActivation { name : "swish", activation_fn : swish, activation_prime_fn : swish_prime },
+    FnActivation { name : SOFTMAX, activation_fn : default, activation_prime_fn : default_prime },
+    FnActivation { name : "linear", activation_fn : linear, activation_prime_fn : linear_prime }
+];
+
+// Leaky relu whose slope below 0 is learned, parameters are [slope].
+struct Prelu;
+
+impl Activation for Prelu {
+    fn get_name(&self) -> &str {
+        PRELU
+    }
+
+    fn activate(&self, x : f64, parameters : &[f64]) -> f64 {
+        if x < 0. {
+            return parameters[0] * x
+        }
+        x
     }
 
-    pub fn set_fn_code (&mut self, code : usize) {
-        if code > self.activation_fns.len() {
-            panic!(set_act_fn_code_out_of_bounds());
+    fn derivative(&self, x : f64, parameters : &[f64]) -> f64 {
+        if x < 0. {
+            return parameters[0]
         }
-        self.activation_fn_code = code;
+        1.
     }
 
-    pub fn get_fn_code_by_name (&mut self, fn_name : &String) -> usize {
-        for (i, name) in self.fn_names.iter().enumerate() {
-            if fn_name == name {
-                return i
-            }
+    fn get_default_parameters(&self) -> Vec<f64> {
+        vec![0.25]
+    }
+
+    fn parameter_derivatives(&self, x : f64, _parameters : &[f64], derivatives : &mut [f64]) {
+        derivatives[0] = if x < 0. { x } else { 0. };
+    }
+}
+
+// Elu whose scale below 0 is learned, parameters are [alpha].
+struct Pelu;
+
+impl Activation for Pelu {
+    fn get_name(&self) -> &str {
+        PELU
+    }
+
+    fn activate(&self, x : f64, parameters : &[f64]) -> f64 {
+        if x <= 0.0 {
+            parameters[0] * (x.exp() - 1.0)
+        } else {
+            x
         }
-        panic!(fn_name_not_found());
     }
-/*
-    pub fn get_fn_name_from_code(&mut self, fn_code : usize) -> String {
-        if fn_code > self.fn_names.len() {
-           panic!("Activator cannot find activation function from code number {}!", fn_code);
+
+    fn derivative(&self, x : f64, parameters : &[f64]) -> f64 {
+        if x <= 0.0 {
+            parameters[0] * x.exp()
+        } else {
+            1.0
         }
-        let bytes = &self.fn_names[fn_code].into_bytes();
-        String::from_utf8(bytes.to_vec()).expect("Found invalid UTF-8")
     }
-*/
+
+    fn get_default_parameters(&self) -> Vec<f64> {
+        vec![1.0]
+    }
+
+    fn parameter_derivatives(&self, x : f64, _parameters : &[f64], derivatives : &mut [f64]) {
+        derivatives[0] = if x <= 0.0 { x.exp() - 1.0 } else { 0.0 };
+    }
+}
+
+// x * sigmoid(beta * x) with beta learned, parameters are [beta].
+// A beta of 1 is plain swish.
+struct Pswish;
+
+impl Activation for Pswish {
+    fn get_name(&self) -> &str {
+        PSWISH
+    }
+
+    fn activate(&self, x : f64, parameters : &[f64]) -> f64 {
+        x * sigmoid(parameters[0] * x)
+    }
+
+    fn derivative(&self, x : f64, parameters : &[f64]) -> f64 {
+        let beta : f64 = parameters[0];
+        let sig : f64 = sigmoid(beta * x);
+        sig + beta * x * sig * (1. - sig)
+    }
+
+    fn get_default_parameters(&self) -> Vec<f64> {
+        vec![1.0]
+    }
+
+    fn parameter_derivatives(&self, x : f64, parameters : &[f64], derivatives : &mut [f64]) {
+        let sig : f64 = sigmoid(parameters[0] * x);
+        derivatives[0] = x * x * sig * (1. - sig);
+    }
 }
 
 fn default(x : f64) -> f64 {
     x
 }
 
-fn default_prime(x : f64) -> f64 {
-    x
+fn default_prime(_x : f64) -> f64 {
+    1.
 }
 
 fn sigmoid(x : f64) -> f64 {
@@ -128,14 +287,14 @@ fn binary_step(x : f64) -> f64 {
     return -1.
 }
 
-fn binary_step_prime(x : f64) -> f64 {
-    x
+// flat everywhere except the jump at 0
+fn binary_step_prime(_x : f64) -> f64 {
+    0.
 }
 
+// e^x / e^-x overflow to inf / inf for large |x|, the library tanh saturates instead
 fn tanh(x : f64) -> f64 {
-    let a = E.powf(x);
-    let b = E.powf(-x);
-    (a - b) / (a + b)
+    x.tanh()
 }
 
 fn tanh_prime(x : f64) -> f64 {
@@ -143,13 +302,9 @@ fn tanh_prime(x : f64) -> f64 {
     1. - tanh.powi(2)
 }
 
-fn sech(x : f64) -> f64 {
-    2.0 / (E.powf(x) + E.powf(-x))
-}
-
 fn sqnl(x : f64) -> f64 {
     if x > 2. {
-        return 0.
+        return 1.
     } else if (0. <= x) & (x <= 2.) {
         return x - (x.powi(2) / 4.)
     } else if (-2. <= x) & (x < 0.) {
@@ -165,7 +320,7 @@ fn sqnl_prime(x : f64) -> f64 {
     } else if (0. <= x) & (x <= 2.) {
         return 1. - (x / 2.)
     } else if (-2. <= x) & (x < 0.) {
-        return x + (x / 2.)
+        return 1. + (x / 2.)
     } else {
         return 0.
     }
@@ -209,9 +364,11 @@ fn elu_prime(x : f64) -> f64 {
     }
 }
 
+// ALPHA is the scale and LAMBDA the alpha of the selu paper,
+// so both branches are scaled by ALPHA
 fn selu(x : f64) -> f64 {
     if x <= 0.0 {
-        LAMBDA * (E.powf( x ) - 1.0)
+        ALPHA * LAMBDA * (E.powf( x ) - 1.0)
     } else {
         ALPHA * x
     }
@@ -219,7 +376,7 @@ fn selu(x : f64) -> f64 {
 
 fn selu_prime(x : f64) -> f64 {
     if x <= 0.0 {
-        elu(x) + LAMBDA
+        ALPHA * LAMBDA * E.powf( x )
     } else {
         ALPHA
     }
@@ -229,18 +386,17 @@ fn gelu(x : f64) -> f64 {
     0.5 * x * (1.0 + tanh((2.0 / PI).sqrt() * (x + (0.044715 * ( x * x * x )))))
 }
 
+// sech^2 is written as 1 - tanh^2 so it can't overflow
 fn gelu_prime(x : f64) -> f64  {
-    let term1 = 0.0356774 * x * x * x;
-    let term2 = 0.398942 * x;
-    let term3 = 0.797885 * x;
-    let term4 = 0.0535161  * x * x * x;
-    let mut hyp_secant = sech(term1 + term3);
-    hyp_secant *= hyp_secant;
-    0.5 * tanh(term1 + term3) + (term4 + term2) * hyp_secant + 0.5
+    let scale : f64 = (2.0 / PI).sqrt();
+    let tanh_inner : f64 = tanh(scale * (x + (0.044715 * ( x * x * x ))));
+    let inner_prime : f64 = scale * (1.0 + 3.0 * 0.044715 * x * x);
+    0.5 * (1.0 + tanh_inner) + 0.5 * x * (1.0 - tanh_inner * tanh_inner) * inner_prime
 }
 
+// ln(1 + e^x) = max(x, 0) + ln(1 + e^-|x|), which keeps e^x from overflowing
 fn softplus(x : f64) -> f64 {
-    (1.0 + E.powf( x )).ln()
+    x.max(0.0) + (-x.abs()).exp().ln_1p()
 }
 
 fn softplus_prime(x : f64) -> f64 {
@@ -253,4 +409,12 @@ fn swish(x : f64) -> f64 {
 
 fn swish_prime(x : f64) -> f64 {
     x * sigmoid_prime(x) + sigmoid(x)
-}
\ No newline at end of file
+}
+
+fn linear(x : f64) -> f64 {
+    x
+}
+
+fn linear_prime(_x : f64) -> f64 {
+    1.
+}
@@ -0,0 +1,88 @@
+use super::super::data_and_config::neural_net_config_parts::{F64, F32};
+use std::fmt::Debug;
+use std::ops::{Add, Sub, Mul, Div, Neg, AddAssign, SubAssign, MulAssign, DivAssign};
+
+// The float types a layer can keep its weights and state in. Settings, data and the
+// activation and loss functions stay in f64, values are converted as they cross over.
+pub trait Float :
+    Copy + Debug + PartialOrd + Send + Sync + 'static
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> + Neg<Output = Self>
+    + AddAssign + SubAssign + MulAssign + DivAssign
+{
+    // the name settings use for this type
+    fn precision() -> &'static str;
+    fn from_f64(value : f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn sqrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn max(self, other : Self) -> Self;
+    fn is_finite(self) -> bool;
+}
+
+impl Float for f64 {
+    fn precision() -> &'static str {
+        F64
+    }
+
+    fn from_f64(value : f64) -> f64 {
+        value
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn sqrt(self) -> f64 {
+        f64::sqrt(self)
+    }
+
+    fn exp(self) -> f64 {
+        f64::exp(self)
+    }
+
+    fn max(self, other : f64) -> f64 {
+        f64::max(self, other)
+    }
+
+    fn is_finite(self) -> bool {
+        f64::is_finite(self)
+    }
+}
+
+impl Float for f32 {
+    fn precision() -> &'static str {
+        F32
+    }
+
+    fn from_f64(value : f64) -> f32 {
+        value as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn sqrt(self) -> f32 {
+        f32::sqrt(self)
+    }
+
+    fn exp(self) -> f32 {
+        f32::exp(self)
+    }
+
+    fn max(self, other : f32) -> f32 {
+        f32::max(self, other)
+    }
+
+    fn is_finite(self) -> bool {
+        f32::is_finite(self)
+    }
+}
+
+pub fn from_f64_values<F : Float>(values : &[f64]) -> Vec<F> {
+    values.iter().map(|value| F::from_f64(*value)).collect()
+}
+
+pub fn to_f64_values<F : Float>(values : &[F]) -> Vec<f64> {
+    values.iter().map(|value| value.to_f64()).collect()
+}
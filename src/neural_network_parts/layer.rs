@@ -1,138 +1,802 @@
-use super::activator::Activator;
-use super::node::Node;
-use super::super::data_and_config::neural_net_config_parts::LayerSettings;
-
-pub struct Layer {
-    learning_rate : f64,
-    activation_function_code : usize,
-    momentum : f64,
+use super::activator::{Activation, SOFTMAX, SIGMOID};
+use super::loss::Loss;
+use super::optimizer::Optimizer;
+use super::learning_rate_schedule::LearningRateSchedule;
+use super::layer_weights::{get_layer_biases, check_layer_weights, get_weights_per_node, make_layer_weights};
+use super::matrix::{mat_mat, transposed_mat_vec, add_outer_product};
+use super::float::{Float, from_f64_values, to_f64_values};
+use super::super::tinman_error::TinmanError;
+use super::super::data_and_config::neural_net_config_parts::{LayerSettings, DENSE, BATCH_NORM};
+use rand::{Rng, StdRng};
+use std::sync::Arc;
+use std::ops::Neg;
+
+const RUNNING_STATS_MOMENTUM : f64 = 0.99;
+const BATCH_NORM_EPSILON : f64 = 1e-5;
+
+// Everything a layer keeps per unit is stored in flat vectors. The weights and their
+// gradients and optimizer state are row-major matrices, a row of weights_per_unit
+// columns per unit, so the passes over them read memory in order.
+// F is the float type that state is kept in, the settings stay f64.
+pub struct Layer<F : Float> {
+    optimizer : Optimizer,
+    schedule : LearningRateSchedule,
+    activation : Arc<dyn Activation>,
+    // learned parameters of a parametric activation, shared by every unit
+    activation_parameters : Vec<f64>,
+    parameter_velocities : Vec<f64>,
+    parameter_caches : Vec<f64>,
+    parameter_gradients : Vec<f64>,
+    parameter_derivatives : Vec<f64>,
+    softmax : bool,
+    sigmoid : bool,
     bias : f64,
+    l1 : f64,
+    l2 : f64,
+    max_norm : Option<f64>,
+    dropout_rate : f64,
+    batch_norm : bool,
+    // whether the last forward pass normalized with the statistics of its batch
+    batch_statistics : bool,
+    running_mean : Vec<F>,
+    running_variance : Vec<F>,
+    normalized_inputs : Vec<F>,
+    inverse_stds : Vec<F>,
+    init : Option<String>,
     high : f64,
     low : f64,
-    prev_layer_first_node_idx : usize,
-    nodes_start_index : usize,
-    nodes_stop_index : usize
+    input_size : usize,
+    weights_per_unit : usize,
+    weights : Vec<F>,
+    weight_velocities : Vec<F>,
+    weight_caches : Vec<F>,
+    weight_gradients : Vec<F>,
+    biases : Vec<F>,
+    bias_velocities : Vec<F>,
+    bias_caches : Vec<F>,
+    bias_gradients : Vec<F>,
+    // The values below are kept for every row of the last forward pass,
+    // a row of units after another (a row of inputs for input_errors).
+    row_count : usize,
+    sums : Vec<F>,
+    outputs : Vec<F>,
+    output_primes : Vec<F>,
+    dropout_masks : Vec<F>,
+    // error reaching each output, before the activation's derivative is applied
+    output_errors : Vec<F>,
+    deltas : Vec<F>,
+    // error reaching each input, before the layer below applies its derivative
+    input_errors : Vec<F>
 }
 
-impl Layer {
-    pub fn new(settings : &LayerSettings, input_size : usize, fn_code : usize, start : usize) -> Layer {
-        Layer {
-            learning_rate : settings.learning_rate,
-            activation_function_code : fn_code,
-            momentum : settings.momentum,
+impl<F : Float> Layer<F> {
+    pub fn new(
+        settings : &LayerSettings,
+        input_size : usize,
+        activation : Arc<dyn Activation>,
+        layer : usize,
+        rng : &mut StdRng
+    ) -> Result<Layer<F>, TinmanError>
+    {
+        let activation_parameters : Vec<f64> = get_activation_parameters(settings, &*activation, layer)?;
+        let parameter_count : usize = activation_parameters.len();
+        let units : usize = settings.output_units;
+        let biases : Vec<F> = from_f64_values(&get_layer_biases(settings, layer)?);
+        let weights : Vec<F> = from_f64_values(&make_layer_weights(settings, input_size, layer, rng)?.concat());
+        let weights_per_unit : usize = get_weights_per_node(settings, input_size, layer)?;
+        let (running_mean, running_variance) = get_running_stats(settings, layer)?;
+        let weight_count : usize = weights.len();
+        let zero : F = F::from_f64(0.0);
+        let one : F = F::from_f64(1.0);
+        Ok(Layer {
+            optimizer : Optimizer::new(settings, layer)?,
+            schedule : LearningRateSchedule::new(settings, layer)?,
+            softmax : activation.get_name() == SOFTMAX,
+            sigmoid : activation.get_name() == SIGMOID,
+            activation,
+            activation_parameters,
+            parameter_velocities : vec![0.0; parameter_count],
+            parameter_caches : vec![0.0; parameter_count],
+            parameter_gradients : vec![0.0; parameter_count],
+            parameter_derivatives : vec![0.0; parameter_count],
             bias : settings.bias,
+            l1 : settings.l1.unwrap_or(0.0),
+            l2 : settings.l2.unwrap_or(0.0),
+            max_norm : settings.max_norm,
+            dropout_rate : settings.dropout_rate.unwrap_or(0.0),
+            batch_norm : settings.is_batch_norm(),
+            batch_statistics : false,
+            running_mean : from_f64_values(&running_mean),
+            running_variance : from_f64_values(&running_variance),
+            normalized_inputs : vec![zero; units],
+            inverse_stds : vec![one; units],
+            init : settings.init.clone(),
             high : settings.weight_range[1],
             low : settings.weight_range[0],
-            prev_layer_first_node_idx : start - input_size,
-            nodes_start_index : start,
-            nodes_stop_index : start + settings.output_units
+            input_size,
+            weights_per_unit,
+            weights,
+            weight_velocities : vec![zero; weight_count],
+            weight_caches : vec![zero; weight_count],
+            weight_gradients : vec![zero; weight_count],
+            biases,
+            bias_velocities : vec![zero; units],
+            bias_caches : vec![zero; units],
+            bias_gradients : vec![zero; units],
+            row_count : 1,
+            sums : vec![zero; units],
+            outputs : vec![zero; units],
+            output_primes : vec![zero; units],
+            dropout_masks : vec![one; units],
+            output_errors : vec![zero; units],
+            deltas : vec![zero; units],
+            input_errors : vec![zero; input_size]
+        })
+    }
+
+    // Inputs are the network's inputs for the first layer and the outputs of the layer below otherwise,
+    // row_count rows one after another. Batch norm layers only normalize with the statistics of the
+    // batch while training, dropout is only applied when the network passes an rng to draw the masks from.
+    pub fn forward(
+        &mut self,
+        inputs : &[F],
+        row_count : usize,
+        training : bool,
+        dropout : Option<&mut StdRng>
+    )
+    {
+        self.set_row_count(row_count);
+        if self.batch_norm {
+            self.batch_norm_forward(inputs, training);
+        } else {
+            mat_mat(&self.weights, &self.biases, inputs, self.weights_per_unit, &mut self.sums);
+        }
+        self.activate();
+        if self.softmax {
+            self.apply_softmax();
+        }
+        self.set_dropout_masks(dropout);
+    }
+
+    // Inference only forward pass over many rows, inputs holds one row after another
+    // and so do the returned outputs. Nothing the layer keeps is changed.
+    pub fn forward_batch(&self, inputs : &[F], row_count : usize) -> Vec<F> {
+        let units : usize = self.get_output_units();
+        let mut outputs : Vec<F> = vec![F::from_f64(0.0); row_count * units];
+        if self.batch_norm {
+            for (input_row, output_row) in inputs.chunks_exact(units.max(1)).zip(outputs.chunks_exact_mut(units.max(1))) {
+                for (k, (input, output)) in input_row.iter().zip(output_row.iter_mut()).enumerate() {
+                    let inverse_std : F = F::from_f64(1.0) / (self.running_variance[k] + F::from_f64(BATCH_NORM_EPSILON)).sqrt();
+                    let normalized : F = (*input - self.running_mean[k]) * inverse_std;
+                    *output = self.weights[k] * normalized + self.biases[k];
+                }
+            }
+        } else {
+            mat_mat(&self.weights, &self.biases, inputs, self.weights_per_unit, &mut outputs);
         }
+        for output in outputs.iter_mut() {
+            *output = F::from_f64(self.activation.activate(output.to_f64(), &self.activation_parameters));
+        }
+        if self.softmax {
+            for output_row in outputs.chunks_exact_mut(units.max(1)) {
+                softmax(output_row);
+            }
+        }
+        outputs
     }
 
-    pub fn input_layer_forward(&mut self, inputs : &Vec<f64>, nodes : &mut Vec<Node>, activator : &mut Activator) {
-        activator.set_fn_code(self.activation_function_code);
-        for i in self.nodes_start_index .. self.nodes_stop_index {
-            nodes[i].input_layer_forward(inputs, activator, self.bias);
+    // The per-row state only changes size when the batch does.
+    fn set_row_count(&mut self, row_count : usize) {
+        if row_count == self.row_count {
+            return;
         }
+        let values : usize = row_count * self.get_output_units();
+        let zero : F = F::from_f64(0.0);
+        self.row_count = row_count;
+        self.sums.resize(values, zero);
+        self.outputs.resize(values, zero);
+        self.output_primes.resize(values, zero);
+        self.dropout_masks.resize(values, F::from_f64(1.0));
+        self.output_errors.resize(values, zero);
+        self.deltas.resize(values, zero);
+        self.normalized_inputs.resize(values, zero);
+        self.input_errors.resize(row_count * self.input_size, zero);
     }
 
-    pub fn hidden_layer_forward(&mut self, nodes : &mut Vec<Node>, activator : &mut Activator) {
-        activator.set_fn_code(self.activation_function_code);
-        for i in self.nodes_start_index .. self.nodes_stop_index {
-            let mut result : f64 = 0.0;
-            for j in self.prev_layer_first_node_idx .. self.nodes_start_index {
-                result += nodes[i].get_weight_at(j - self.prev_layer_first_node_idx) * nodes[j].get_activated_output();
+    // While training, each unit is normalized with the mean and variance of its inputs
+    // over the batch, and the running statistics follow those for inference. A single
+    // row has no spread to normalize by, so it uses the running statistics like inference
+    // does. The normalized values are then scaled by gamma (the unit's only weight) and
+    // shifted by beta (its bias).
+    fn batch_norm_forward(&mut self, inputs : &[F], training : bool) {
+        let units : usize = self.get_output_units();
+        let epsilon : F = F::from_f64(BATCH_NORM_EPSILON);
+        self.batch_statistics = training && self.row_count > 1;
+        for k in 0 .. units {
+            let (mean, variance) = if self.batch_statistics {
+                let (mean, variance) = column_mean_and_variance(inputs, units, k);
+                self.update_running_stats(k, mean, variance);
+                (mean, variance)
+            } else {
+                (self.running_mean[k], self.running_variance[k])
+            };
+            let inverse_std : F = F::from_f64(1.0) / (variance + epsilon).sqrt();
+            self.inverse_stds[k] = inverse_std;
+            for i in (k .. inputs.len()).step_by(units) {
+                let normalized : F = (inputs[i] - mean) * inverse_std;
+                self.normalized_inputs[i] = normalized;
+                self.sums[i] = self.weights[k] * normalized + self.biases[k];
             }
-            let activated : f64 = activator.activate(result);
-            nodes[i].set_activated_output(activated);
-            let activated_prime : f64 = activator.activate_prime(result);
-            nodes[i].set_activated_prime_output(activated_prime);
         }
     }
 
-    pub fn set_delta(&mut self, correct_index : usize, nodes : &mut Vec<Node>) {
-        let mut yhat : f64;
-        for i in self.nodes_start_index .. self.nodes_stop_index {
-            let output_prime : f64 = nodes[i].get_activated_prime_output();
-            let output : f64 = nodes[i].get_activated_output();
-            if i - self.nodes_start_index == correct_index {
-                yhat = 1.0;
+    // the running variance is kept unbiased, the batch variance divides by the row count
+    fn update_running_stats(&mut self, unit : usize, mean : F, variance : F) {
+        let momentum : F = F::from_f64(RUNNING_STATS_MOMENTUM);
+        let one_minus_momentum : F = F::from_f64(1.0 - RUNNING_STATS_MOMENTUM);
+        let rows : f64 = self.row_count as f64;
+        let unbiased_variance : F = variance * F::from_f64(rows / (rows - 1.0));
+        self.running_mean[unit] = momentum * self.running_mean[unit] + one_minus_momentum * mean;
+        self.running_variance[unit] = momentum * self.running_variance[unit] + one_minus_momentum * unbiased_variance;
+    }
+
+    fn activate(&mut self) {
+        let sums = self.sums.iter().zip(self.outputs.iter_mut()).zip(self.output_primes.iter_mut());
+        for ((sum, output), output_prime) in sums {
+            let x : f64 = sum.to_f64();
+            *output = F::from_f64(self.activation.activate(x, &self.activation_parameters));
+            *output_prime = F::from_f64(self.activation.derivative(x, &self.activation_parameters));
+        }
+    }
+
+    fn apply_softmax(&mut self) {
+        let units : usize = self.get_output_units();
+        for output_row in self.outputs.chunks_exact_mut(units.max(1)) {
+            softmax(output_row);
+        }
+        let one : F = F::from_f64(1.0);
+        for (output, output_prime) in self.outputs.iter().zip(self.output_primes.iter_mut()) {
+            *output_prime = *output * (one - *output);
+        }
+    }
+
+    // Inverted dropout, each unit is dropped with probability dropout_rate and the
+    // survivors are scaled up so the expected output matches inference.
+    fn set_dropout_masks(&mut self, dropout : Option<&mut StdRng>) {
+        let rng : &mut StdRng = match dropout {
+            Some(rng) if self.dropout_rate > 0.0 => rng,
+            _ => {
+                for mask in self.dropout_masks.iter_mut() {
+                    *mask = F::from_f64(1.0);
+                }
+                return;
+            }
+        };
+        let keep_scale : F = F::from_f64(1.0 / (1.0 - self.dropout_rate));
+        for (mask, output) in self.dropout_masks.iter_mut().zip(self.outputs.iter_mut()) {
+            if rng.gen::<f64>() < self.dropout_rate {
+                *mask = F::from_f64(0.0);
             } else {
-                yhat = 0.0;
+                *mask = keep_scale;
             }
-            nodes[i].set_delta((output - yhat) * output_prime);
+            *output *= *mask;
         }
     }
 
-    pub fn get_prediction(&mut self, nodes : &mut Vec<Node>) -> i64 {
-        let mut max_idx : i64 = -1;
-        let mut max_val = 0.0;
-        for i in self.nodes_start_index .. self.nodes_stop_index {
-            let current : f64 = nodes[i].get_activated_output();
-            if current > max_val {
-                max_val = current;
-                max_idx = i as i64;
+    // targets has a row for every row of the last forward pass
+    pub fn set_delta(&mut self, targets : &[f64], loss : &Loss) {
+        if self.softmax {
+            let units : usize = self.get_output_units().max(1);
+            let rows = self.deltas.chunks_exact_mut(units).zip(self.outputs.chunks_exact(units)).zip(targets.chunks_exact(units));
+            for ((deltas, outputs), targets) in rows {
+                set_softmax_deltas(deltas, outputs, targets, loss);
             }
+            return;
         }
-        if max_idx < self.nodes_start_index as i64 {
-            max_idx = self.nodes_start_index as i64;
+        let nodes = self.deltas.iter_mut().zip(self.output_errors.iter_mut()).zip(self.outputs.iter().zip(self.output_primes.iter()));
+        for (((delta, output_error), (output, output_prime)), target) in nodes.zip(targets) {
+            let output : f64 = output.to_f64();
+            if self.sigmoid && loss.is_binary_cross_entropy() {
+                // sigmoid prime cancels the denominator of the loss gradient
+                *delta = F::from_f64(output - *target);
+            } else {
+                *output_error = F::from_f64(loss.loss_prime(output, *target));
+                *delta = *output_error * *output_prime;
+            }
+        }
+    }
+
+    pub fn get_outputs(&self) -> Vec<f64> {
+        to_f64_values(&self.outputs)
+    }
+
+    pub fn get_output_slice(&self) -> &[F] {
+        &self.outputs
+    }
+
+    pub fn get_output_units(&self) -> usize {
+        self.biases.len()
+    }
+
+    pub fn get_row_count(&self) -> usize {
+        self.row_count
+    }
+
+    pub fn is_batch_norm(&self) -> bool {
+        self.batch_norm
+    }
+
+    // Only adds to the gradient sums, weights change when apply_gradients is called.
+    // With pass_errors the error reaching each input is left in input_errors
+    // for the layer below to take with set_deltas_from_errors.
+    pub fn backward(&mut self, inputs : &[F], pass_errors : bool) {
+        let units : usize = self.get_output_units().max(1);
+        if self.batch_norm {
+            self.batch_norm_backward(pass_errors);
         } else {
-            max_idx -= self.nodes_start_index as i64;
-        }
-        max_idx
-    }
-
-    pub fn hidden_layer_backward(&mut self, nodes : &mut Vec<Node>) {
-        for i in self.prev_layer_first_node_idx .. self.nodes_start_index {
-            let mut error_for_node = 0.0;
-            for j in self.nodes_start_index .. self.nodes_stop_index {
-                let adjusted_idx : usize = i - self.prev_layer_first_node_idx;
-                let delta : f64 = nodes[j].get_delta();
-                error_for_node += delta * nodes[j].get_weight_at(adjusted_idx);
-                let delta_w = nodes[i].get_activated_output() * delta;
-                let momentum_adjustment : f64 = self.momentum * nodes[j].get_prev_weight_at(adjusted_idx);
-                let adjusted_delta_w : f64 = self.learning_rate * delta_w;
-                nodes[j].set_weight_at(adjusted_idx, adjusted_delta_w + momentum_adjustment);
-                nodes[j].set_prev_weight_at(adjusted_idx, adjusted_delta_w + momentum_adjustment);
+            let input_size : usize = self.input_size.max(1);
+            let rows = self.deltas.chunks_exact(units)
+                .zip(inputs.chunks_exact(input_size))
+                .zip(self.input_errors.chunks_exact_mut(input_size));
+            for ((deltas, inputs), input_errors) in rows {
+                if pass_errors {
+                    transposed_mat_vec(&self.weights, deltas, input_errors);
+                }
+                add_outer_product(&mut self.weight_gradients, deltas, inputs);
+            }
+        }
+        for deltas in self.deltas.chunks_exact(units) {
+            for (bias_gradient, delta) in self.bias_gradients.iter_mut().zip(deltas) {
+                *bias_gradient += *delta;
+            }
+        }
+        if !self.activation_parameters.is_empty() {
+            self.add_parameter_gradients();
+        }
+    }
+
+    // The sums from the forward pass are still in place, so each unit's
+    // share of the parameter gradients is worked out from them here.
+    fn add_parameter_gradients(&mut self) {
+        for (sum, output_error) in self.sums.iter().zip(&self.output_errors) {
+            self.activation.parameter_derivatives(sum.to_f64(), &self.activation_parameters, &mut self.parameter_derivatives);
+            let output_error : f64 = output_error.to_f64();
+            for (gradient, derivative) in self.parameter_gradients.iter_mut().zip(&self.parameter_derivatives) {
+                *gradient += output_error * derivative;
+            }
+        }
+    }
+
+    // With batch statistics every normalized value depends on all the inputs of its unit
+    // in the batch, so the error goes back through the mean and variance as well:
+    // dx_i = gamma * inverse_std * (dy_i - (sum dy + x^_i * sum dy * x^) / rows)
+    // The running statistics are constants, so each row only reaches its own input.
+    fn batch_norm_backward(&mut self, pass_errors : bool) {
+        let units : usize = self.get_output_units();
+        let rows : F = F::from_f64(self.row_count as f64);
+        let values : usize = self.deltas.len();
+        for k in 0 .. units {
+            let mut delta_sum : F = F::from_f64(0.0);
+            let mut weighted_sum : F = F::from_f64(0.0);
+            for i in (k .. values).step_by(units) {
+                delta_sum += self.deltas[i];
+                weighted_sum += self.deltas[i] * self.normalized_inputs[i];
+            }
+            self.weight_gradients[k] += weighted_sum;
+            if !pass_errors {
+                continue;
+            }
+            let scale : F = self.weights[k] * self.inverse_stds[k];
+            for i in (k .. values).step_by(units) {
+                self.input_errors[i] = if self.batch_statistics {
+                    scale * (self.deltas[i] - (delta_sum + self.normalized_inputs[i] * weighted_sum) / rows)
+                } else {
+                    scale * self.deltas[i]
+                };
             }
-            let prev_output_prime : f64 = nodes[i].get_activated_prime_output();
-            nodes[i].set_delta(error_for_node * prev_output_prime);
         }
     }
 
-    pub fn input_layer_backward(&mut self, inputs : &Vec<f64>, nodes : &mut Vec<Node>) {
-        for i in 0 .. inputs.len() {
-            for j in self.nodes_start_index .. self.nodes_stop_index {
-                let delta_w : f64 = nodes[j].get_delta() * inputs[i];
-                let momentum_adjustment : f64 = self.momentum * nodes[j].get_prev_weight_at(i);
-                let adjusted_delta_w : f64 = self.learning_rate * delta_w;
-                nodes[j].set_weight_at(i, (self.learning_rate * delta_w) + momentum_adjustment);
-                nodes[j].set_prev_weight_at(i, adjusted_delta_w + momentum_adjustment);
+    pub fn get_input_errors(&self) -> &[F] {
+        &self.input_errors
+    }
+
+    // dropped units pass no error back
+    pub fn set_deltas_from_errors(&mut self, errors : &[F]) {
+        let nodes = self.deltas.iter_mut().zip(self.output_errors.iter_mut()).zip(self.output_primes.iter().zip(self.dropout_masks.iter()));
+        for (((delta, output_error), (output_prime, dropout_mask)), error) in nodes.zip(errors) {
+            *output_error = *error * *dropout_mask;
+            *delta = *error * (*output_prime * *dropout_mask);
+        }
+    }
+
+    // Applies the mean of the gradients summed since the last call, then clears them.
+    // The l1 and l2 penalties are added to the weight gradients, the biases are left alone.
+    // Gamma and beta of a batch norm layer only rescale and shift, so they get no penalties or max norm.
+    pub fn apply_gradients(&mut self, batch_count : usize) {
+        self.optimizer.next_time_step();
+        let batch_count : F = F::from_f64(batch_count as f64);
+        let zero : F = F::from_f64(0.0);
+        let (l1, l2) = if self.batch_norm {
+            (zero, zero)
+        } else {
+            (F::from_f64(self.l1), F::from_f64(self.l2))
+        };
+        for (gradient, weight) in self.weight_gradients.iter_mut().zip(&self.weights) {
+            // weight signs are random, so the l1 term is picked without a branch
+            let sign : F = F::from_f64(((*weight > zero) as i32 - (*weight < zero) as i32) as f64);
+            let penalty : F = l2 * *weight + l1 * sign;
+            *gradient = *gradient / batch_count + penalty;
+        }
+        for gradient in self.bias_gradients.iter_mut() {
+            *gradient /= batch_count;
+        }
+        // the optimizer turns the gradients into the changes to make
+        self.optimizer.step(&mut self.weight_gradients, &mut self.weight_velocities, &mut self.weight_caches);
+        self.optimizer.step(&mut self.bias_gradients, &mut self.bias_velocities, &mut self.bias_caches);
+        for (weight, change) in self.weights.iter_mut().zip(self.weight_gradients.iter_mut()) {
+            *weight -= *change;
+            *change = zero;
+        }
+        for (bias, change) in self.biases.iter_mut().zip(self.bias_gradients.iter_mut()) {
+            *bias -= *change;
+            *change = zero;
+        }
+        if !self.activation_parameters.is_empty() {
+            self.apply_parameter_gradients(batch_count.to_f64());
+        }
+        if let Some(max_norm) = self.max_norm.filter(|_| !self.batch_norm) {
+            self.apply_max_norm(max_norm);
+        }
+    }
+
+    // the activation parameters get no penalties and are always kept in f64
+    fn apply_parameter_gradients(&mut self, batch_count : f64) {
+        for gradient in self.parameter_gradients.iter_mut() {
+            *gradient /= batch_count;
+        }
+        self.optimizer.step(&mut self.parameter_gradients, &mut self.parameter_velocities, &mut self.parameter_caches);
+        for (parameter, change) in self.activation_parameters.iter_mut().zip(self.parameter_gradients.iter_mut()) {
+            *parameter -= *change;
+            *change = 0.0;
+        }
+    }
+
+    // scales each unit's weights back down if their length grew past max_norm
+    fn apply_max_norm(&mut self, max_norm : f64) {
+        let max_norm : F = F::from_f64(max_norm);
+        for row in self.weights.chunks_exact_mut(self.weights_per_unit.max(1)) {
+            let mut squared_sum : F = F::from_f64(0.0);
+            for weight in row.iter() {
+                squared_sum += *weight * *weight;
+            }
+            let norm : F = squared_sum.sqrt();
+            if norm > max_norm {
+                let scale : F = max_norm / norm;
+                for weight in row.iter_mut() {
+                    *weight *= scale;
+                }
             }
         }
     }
 
+    // The learned values are the weights, then the biases, then the activation parameters,
+    // indexed as one list. Their gradients are the sums since the last apply_gradients.
+    pub fn get_learned_gradients(&self) -> Vec<f64> {
+        let mut gradients : Vec<f64> = to_f64_values(&self.weight_gradients);
+        gradients.extend(to_f64_values(&self.bias_gradients));
+        gradients.extend_from_slice(&self.parameter_gradients);
+        gradients
+    }
+
+    pub fn set_learned_gradients(&mut self, gradients : &[f64]) {
+        let (weight_gradients, rest) = gradients.split_at(self.weight_gradients.len());
+        let (bias_gradients, parameter_gradients) = rest.split_at(self.bias_gradients.len());
+        self.weight_gradients = from_f64_values(weight_gradients);
+        self.bias_gradients = from_f64_values(bias_gradients);
+        self.parameter_gradients = parameter_gradients.to_vec();
+    }
+
+    pub fn get_learned_value(&self, index : usize) -> f64 {
+        let bias_start : usize = self.weights.len();
+        let parameter_start : usize = bias_start + self.biases.len();
+        if index < bias_start {
+            self.weights[index].to_f64()
+        } else if index < parameter_start {
+            self.biases[index - bias_start].to_f64()
+        } else {
+            self.activation_parameters[index - parameter_start]
+        }
+    }
+
+    pub fn set_learned_value(&mut self, index : usize, value : f64) {
+        let bias_start : usize = self.weights.len();
+        let parameter_start : usize = bias_start + self.biases.len();
+        if index < bias_start {
+            self.weights[index] = F::from_f64(value);
+        } else if index < parameter_start {
+            self.biases[index - bias_start] = F::from_f64(value);
+        } else {
+            self.activation_parameters[index - parameter_start] = value;
+        }
+    }
+
+    // Clipping works on the gradient sums, the caller scales the limits by the rows summed.
+    pub fn clip_gradients(&mut self, limit : f64) {
+        let weight_limit : F = F::from_f64(limit);
+        for gradient in self.weight_gradients.iter_mut().chain(self.bias_gradients.iter_mut()) {
+            *gradient = clip(*gradient, weight_limit);
+        }
+        for gradient in self.parameter_gradients.iter_mut() {
+            *gradient = clip(*gradient, limit);
+        }
+    }
+
+    pub fn get_gradient_squared_sum(&self) -> f64 {
+        let mut squared_sum : f64 = 0.0;
+        for gradient in self.weight_gradients.iter().chain(&self.bias_gradients) {
+            let gradient : f64 = gradient.to_f64();
+            squared_sum += gradient * gradient;
+        }
+        for gradient in &self.parameter_gradients {
+            squared_sum += gradient * gradient;
+        }
+        squared_sum
+    }
+
+    pub fn scale_gradients(&mut self, scale : f64) {
+        let weight_scale : F = F::from_f64(scale);
+        for gradient in self.weight_gradients.iter_mut().chain(self.bias_gradients.iter_mut()) {
+            *gradient *= weight_scale;
+        }
+        for gradient in self.parameter_gradients.iter_mut() {
+            *gradient *= scale;
+        }
+    }
+
+    pub fn has_finite_weights(&self) -> bool {
+        self.weights.iter().chain(&self.biases).all(|value| value.is_finite())
+            && self.activation_parameters.iter().all(|value| value.is_finite())
+    }
+
+    pub fn has_finite_outputs(&self) -> bool {
+        self.outputs.iter().all(|output| output.is_finite())
+    }
+
+    // round counts from 0
+    pub fn schedule_learning_rate(&mut self, round : usize) {
+        let learning_rate : f64 = self.schedule.learning_rate_for_round(round);
+        self.optimizer.set_learning_rate(learning_rate);
+    }
+
+    pub fn record_loss(&mut self, loss : f64) {
+        self.schedule.record_loss(loss);
+    }
+
+    pub fn get_learning_rate(&self) -> f64 {
+        self.optimizer.get_learning_rate()
+    }
+
+    pub fn get_settings(&self) -> LayerSettings {
+        let layer_weights : Vec<Vec<f64>> = self.weights
+            .chunks_exact(self.weights_per_unit.max(1))
+            .map(|row| to_f64_values(row))
+            .collect();
+        LayerSettings {
+            activation_function : self.activation.get_name().to_string(),
+            activation_parameters : if self.activation_parameters.is_empty() {
+                None
+            } else {
+                Some(self.activation_parameters.clone())
+            },
+            weight_range : [self.low, self.high],
+            init : self.init.clone(),
+            layer_weights : Some(layer_weights),
+            layer_biases : Some(to_f64_values(&self.biases)),
+            output_units : self.get_output_units(),
+            bias : self.bias,
+            learning_rate : self.schedule.get_base_learning_rate(),
+            momentum : self.optimizer.get_momentum(),
+            optimizer : Some(self.optimizer.get_name()),
+            beta1 : Some(self.optimizer.get_beta1()),
+            beta2 : Some(self.optimizer.get_beta2()),
+            epsilon : Some(self.optimizer.get_epsilon()),
+            learning_rate_schedule : self.schedule.get_settings(),
+            l1 : Some(self.l1),
+            l2 : Some(self.l2),
+            max_norm : self.max_norm,
+            dropout_rate : Some(self.dropout_rate),
+            layer_type : Some(self.get_layer_type().to_string()),
+            running_mean : self.get_running_mean(),
+            running_variance : self.get_running_variance()
+        }
+    }
+
+    pub fn get_layer_type(&self) -> &str {
+        if self.batch_norm {
+            BATCH_NORM
+        } else {
+            DENSE
+        }
+    }
+
+    pub fn get_running_mean(&self) -> Option<Vec<f64>> {
+        if self.batch_norm {
+            Some(to_f64_values(&self.running_mean))
+        } else {
+            None
+        }
+    }
+
+    pub fn get_running_variance(&self) -> Option<Vec<f64>> {
+        if self.batch_norm {
+            Some(to_f64_values(&self.running_variance))
+        } else {
+            None
+        }
+    }
+
+    // The settings must fit the nodes the layer already has, it can't be resized.
     pub fn update_state(
         &mut self, 
         default_settings : &LayerSettings, 
-        fn_code : usize,
-        nodes : &mut Vec<Node>
-    )
+        activation : Arc<dyn Activation>,
+        input_size : usize,
+        layer : usize
+    ) -> Result<(), TinmanError>
     {
-        self.learning_rate = default_settings.learning_rate;
-        self.activation_function_code = fn_code;
-        self.momentum = default_settings.momentum;
+        if default_settings.output_units != self.get_output_units() {
+            return Err(TinmanError::UnitCountMismatch {
+                layer,
+                expected : self.get_output_units(),
+                actual : default_settings.output_units
+            });
+        }
+        let weights : &Vec<Vec<f64>> = match &default_settings.layer_weights {
+            Some(weights) => weights,
+            None => return Err(TinmanError::MissingWeights { layer })
+        };
+        // everything is checked before any state changes
+        check_layer_weights(weights, default_settings, input_size, layer)?;
+        let weights_per_unit : usize = get_weights_per_node(default_settings, input_size, layer)?;
+        let biases : Vec<f64> = get_layer_biases(default_settings, layer)?;
+        let (running_mean, running_variance) = get_running_stats(default_settings, layer)?;
+        let activation_parameters : Vec<f64> = get_activation_parameters(default_settings, &*activation, layer)?;
+        self.optimizer = Optimizer::new(default_settings, layer)?;
+        self.schedule = LearningRateSchedule::new(default_settings, layer)?;
+        self.softmax = activation.get_name() == SOFTMAX;
+        self.sigmoid = activation.get_name() == SIGMOID;
+        self.activation = activation;
+        let parameter_count : usize = activation_parameters.len();
+        self.activation_parameters = activation_parameters;
+        self.parameter_velocities = vec![0.0; parameter_count];
+        self.parameter_caches = vec![0.0; parameter_count];
+        self.parameter_gradients = vec![0.0; parameter_count];
+        self.parameter_derivatives = vec![0.0; parameter_count];
         self.bias = default_settings.bias;
+        self.l1 = default_settings.l1.unwrap_or(0.0);
+        self.l2 = default_settings.l2.unwrap_or(0.0);
+        self.max_norm = default_settings.max_norm;
+        self.dropout_rate = default_settings.dropout_rate.unwrap_or(0.0);
+        self.batch_norm = default_settings.is_batch_norm();
+        self.batch_statistics = false;
+        self.running_mean = from_f64_values(&running_mean);
+        self.running_variance = from_f64_values(&running_variance);
+        self.init = default_settings.init.clone();
         self.high = default_settings.weight_range[1];
         self.low = default_settings.weight_range[0];
-        match &default_settings.layer_weights {
-            Some(weights) => {
-                for i in self.nodes_start_index .. self.nodes_stop_index {
-                    nodes[i].update_state(&weights[i- self.nodes_start_index]);
-                }
-            } None => {
-                panic!("attempting to update state, found no weight vectors");
-            }
+        // a batch norm layer has one weight per unit, so the matrix may change shape
+        self.input_size = input_size;
+        self.input_errors = vec![F::from_f64(0.0); self.row_count * input_size];
+        self.weights_per_unit = weights_per_unit;
+        self.weights = from_f64_values(&weights.concat());
+        let weight_count : usize = self.weights.len();
+        let zero : F = F::from_f64(0.0);
+        self.weight_velocities = vec![zero; weight_count];
+        self.weight_caches = vec![zero; weight_count];
+        self.weight_gradients = vec![zero; weight_count];
+        let units : usize = biases.len();
+        self.biases = from_f64_values(&biases);
+        self.bias_velocities = vec![zero; units];
+        self.bias_caches = vec![zero; units];
+        self.bias_gradients = vec![zero; units];
+        Ok(())
+    }
+}
+
+// Every softmax output depends on every sum in the layer, so the loss gradient
+// of a row goes through the full jacobian: delta_i = o_i * (dL/do_i - sum_k dL/do_k * o_k)
+fn set_softmax_deltas<F : Float>(deltas : &mut [F], outputs : &[F], targets : &[f64], loss : &Loss) {
+    if loss.is_cross_entropy() {
+        // the jacobian and the cross-entropy gradient cancel out
+        for ((delta, output), target) in deltas.iter_mut().zip(outputs).zip(targets) {
+            *delta = F::from_f64(output.to_f64() - target);
         }
+        return;
+    }
+    let mut weighted_sum : f64 = 0.0;
+    for (output, target) in outputs.iter().zip(targets) {
+        let output : f64 = output.to_f64();
+        weighted_sum += loss.loss_prime(output, *target) * output;
     }
-}
\ No newline at end of file
+    for ((delta, output), target) in deltas.iter_mut().zip(outputs).zip(targets) {
+        let output : f64 = output.to_f64();
+        *delta = F::from_f64(output * (loss.loss_prime(output, *target) - weighted_sum));
+    }
+}
+
+// Turns the raw sums of a layer into probabilities over the whole layer.
+// The largest sum is subtracted first so exp can't overflow.
+fn softmax<F : Float>(outputs : &mut [F]) {
+    let mut max_sum : F = F::from_f64(f64::NEG_INFINITY);
+    for output in outputs.iter() {
+        max_sum = max_sum.max(*output);
+    }
+    let mut total : F = F::from_f64(0.0);
+    for output in outputs.iter_mut() {
+        *output = (*output - max_sum).exp();
+        total += *output;
+    }
+    for output in outputs.iter_mut() {
+        *output /= total;
+    }
+}
+
+// NaN is passed through, so a diverging gradient is still seen
+fn clip<T : PartialOrd + Neg<Output = T> + Copy>(value : T, limit : T) -> T {
+    if value > limit {
+        limit
+    } else if value < -limit {
+        -limit
+    } else {
+        value
+    }
+}
+
+// mean and variance (divided by the row count) of one unit's inputs over a batch
+fn column_mean_and_variance<F : Float>(inputs : &[F], units : usize, unit : usize) -> (F, F) {
+    let rows : F = F::from_f64((inputs.len() / units) as f64);
+    let mut sum : F = F::from_f64(0.0);
+    for i in (unit .. inputs.len()).step_by(units) {
+        sum += inputs[i];
+    }
+    let mean : F = sum / rows;
+    let mut squared_sum : F = F::from_f64(0.0);
+    for i in (unit .. inputs.len()).step_by(units) {
+        let deviation : F = inputs[i] - mean;
+        squared_sum += deviation * deviation;
+    }
+    (mean, squared_sum / rows)
+}
+
+// Saved running statistics, or a mean of 0 and variance of 1 for a new layer.
+pub fn get_running_stats(settings : &LayerSettings, layer : usize) -> Result<(Vec<f64>, Vec<f64>), TinmanError> {
+    let units : usize = settings.output_units;
+    let running_mean : Vec<f64> = settings.running_mean.clone().unwrap_or_else(|| vec![0.0; units]);
+    let running_variance : Vec<f64> = settings.running_variance.clone().unwrap_or_else(|| vec![1.0; units]);
+    for stats in &[&running_mean, &running_variance] {
+        if stats.len() != units {
+            return Err(TinmanError::RunningStatsLengthMismatch { layer, expected : units, actual : stats.len() });
+        }
+    }
+    Ok((running_mean, running_variance))
+}
+
+// Saved parameters of a parametric activation, or its defaults for a new layer.
+pub fn get_activation_parameters(
+    settings : &LayerSettings,
+    activation : &dyn Activation,
+    layer : usize
+) -> Result<Vec<f64>, TinmanError>
+{
+    let defaults : Vec<f64> = activation.get_default_parameters();
+    match &settings.activation_parameters {
+        Some(parameters) if parameters.len() != defaults.len() => Err(TinmanError::ActivationParameterCountMismatch {
+            layer,
+            expected : defaults.len(),
+            actual : parameters.len()
+        }),
+        Some(parameters) => Ok(parameters.clone()),
+        None => Ok(defaults)
+    }
+}
@@ -0,0 +1,271 @@
+use super::activator::Activation;
+use super::layer::Layer;
+use super::loss::Loss;
+use super::float::{Float, from_f64_values, to_f64_values};
+use super::neural_network::get_activation;
+use super::super::tinman_error::TinmanError;
+use super::super::data_and_config::neural_net_config_parts::{NeuralNetSettings, LayerSettings, F64, F32};
+
+use rand::StdRng;
+use std::sync::Arc;
+
+// What a network needs from its layers, whatever float type they keep their state in.
+// Values go in and come out as f64, a row after another when there are several rows.
+pub trait Layers : Send {
+    fn forward(&mut self, inputs : &[f64], row_count : usize, training : bool, rng : &mut StdRng);
+    fn forward_batch(&self, inputs : &[f64], row_count : usize) -> Vec<f64>;
+    // targets, outputs and losses are for the rows of the last forward pass
+    fn set_delta(&mut self, targets : &[f64], loss : &Loss);
+    fn get_loss(&self, targets : &[f64], loss : &Loss) -> f64;
+    fn get_outputs(&self) -> Vec<f64>;
+    fn get_output_units(&self) -> usize;
+    fn get_row_count(&self) -> usize;
+    fn has_batch_norm(&self) -> bool;
+    // Adds the gradients of the rows to the sums of every layer. Errors are passed down through
+    // the weights the forward pass used, no weight changes until apply_gradients.
+    fn accumulate_gradients(&mut self, inputs : &[f64]);
+    // the optimizer step, the only place weights change
+    fn apply_gradients(&mut self, batch_count : usize);
+    fn schedule_learning_rates(&mut self, round : usize);
+    fn record_loss(&mut self, loss : f64);
+    fn get_learning_rates(&self) -> Vec<f64>;
+    fn get_settings(&self) -> Vec<LayerSettings>;
+    fn update_state(&mut self, settings : &[LayerSettings], activations : Vec<Arc<dyn Activation>>, input_size : usize) -> Result<(), TinmanError>;
+    fn clip_gradients(&mut self, limit : f64);
+    fn get_gradient_norm(&self) -> f64;
+    fn scale_gradients(&mut self, scale : f64);
+    fn has_finite_weights(&self) -> bool;
+    fn has_finite_outputs(&self) -> bool;
+    fn get_learned_gradients(&self) -> Vec<Vec<f64>>;
+    fn set_learned_gradients(&mut self, gradients : &[Vec<f64>]);
+    fn get_learned_value(&self, layer : usize, index : usize) -> f64;
+    fn set_learned_value(&mut self, layer : usize, index : usize, value : f64);
+    fn get_layer_count(&self) -> usize;
+    fn get_precision(&self) -> &'static str;
+}
+
+// Builds the layers in the precision the settings ask for.
+pub fn make_layers(
+    nnet_settings : &NeuralNetSettings,
+    rng : &mut StdRng
+) -> Result<Box<dyn Layers>, TinmanError>
+{
+    match nnet_settings.get_precision() {
+        F64 => Ok(Box::new(LayerStack::<f64>::new(nnet_settings, rng)?)),
+        F32 => Ok(Box::new(LayerStack::<f32>::new(nnet_settings, rng)?)),
+        precision => Err(TinmanError::UnknownPrecision { name : precision.to_string() })
+    }
+}
+
+pub fn check_precision(nnet_settings : &NeuralNetSettings) -> Result<(), TinmanError> {
+    match nnet_settings.get_precision() {
+        F64 | F32 => Ok(()),
+        precision => Err(TinmanError::UnknownPrecision { name : precision.to_string() })
+    }
+}
+
+// The network's inputs are copied into the layers' float type once per pass,
+// each layer then reads the outputs of the one below it.
+struct LayerStack<F : Float> {
+    layers : Vec<Layer<F>>,
+    inputs : Vec<F>
+}
+
+impl<F : Float> LayerStack<F> {
+    fn new(nnet_settings : &NeuralNetSettings, rng : &mut StdRng) -> Result<LayerStack<F>, TinmanError> {
+        let mut input_size : usize = nnet_settings.input_size;
+        let mut layers : Vec<Layer<F>> = Vec::new();
+        for (i, layer_settings) in nnet_settings.layers.iter().enumerate() {
+            let activation : Arc<dyn Activation> = get_activation(layer_settings, i)?;
+            layers.push(Layer::new(layer_settings, input_size, activation, i, rng)?);
+            input_size = layer_settings.output_units;
+        }
+        Ok(LayerStack {
+            layers,
+            inputs : vec![F::from_f64(0.0); nnet_settings.input_size]
+        })
+    }
+
+    fn set_inputs(&mut self, inputs : &[f64]) {
+        self.inputs.clear();
+        self.inputs.extend(inputs.iter().map(|value| F::from_f64(*value)));
+    }
+
+    fn get_output_layer(&self) -> &Layer<F> {
+        &self.layers[self.layers.len() - 1]
+    }
+}
+
+impl<F : Float> Layers for LayerStack<F> {
+    // the output layer never drops nodes
+    fn forward(&mut self, inputs : &[f64], row_count : usize, training : bool, rng : &mut StdRng) {
+        self.set_inputs(inputs);
+        let last_layer_idx : usize = self.layers.len() - 1;
+        for i in 0 .. self.layers.len() {
+            let dropout_rng : Option<&mut StdRng> = if training && i < last_layer_idx { Some(&mut *rng) } else { None };
+            // each layer reads the outputs of the one below it
+            let (earlier, later) = self.layers.split_at_mut(i);
+            let layer_inputs : &[F] = match earlier.last() {
+                Some(prev_layer) => prev_layer.get_output_slice(),
+                None => &self.inputs
+            };
+            later[0].forward(
+                layer_inputs,
+                row_count,
+                training,
+                dropout_rng
+            );
+        }
+    }
+
+    fn forward_batch(&self, inputs : &[f64], row_count : usize) -> Vec<f64> {
+        let mut values : Vec<F> = from_f64_values(inputs);
+        for layer in &self.layers {
+            values = layer.forward_batch(&values, row_count);
+        }
+        to_f64_values(&values)
+    }
+
+    fn set_delta(&mut self, targets : &[f64], loss : &Loss) {
+        let last_layer_idx : usize = self.layers.len() - 1;
+        self.layers[last_layer_idx].set_delta(targets, loss);
+    }
+
+    fn get_loss(&self, targets : &[f64], loss : &Loss) -> f64 {
+        let mut total : f64 = 0.0;
+        for (output, target) in self.get_output_layer().get_output_slice().iter().zip(targets) {
+            total += loss.loss(output.to_f64(), *target);
+        }
+        total
+    }
+
+    fn get_outputs(&self) -> Vec<f64> {
+        self.get_output_layer().get_outputs()
+    }
+
+    fn get_output_units(&self) -> usize {
+        self.get_output_layer().get_output_units()
+    }
+
+    fn get_row_count(&self) -> usize {
+        self.get_output_layer().get_row_count()
+    }
+
+    fn has_batch_norm(&self) -> bool {
+        self.layers.iter().any(|layer| layer.is_batch_norm())
+    }
+
+    fn accumulate_gradients(&mut self, inputs : &[f64]) {
+        self.set_inputs(inputs);
+        for i in (0 .. self.layers.len()).rev() {
+            let (earlier, later) = self.layers.split_at_mut(i);
+            match earlier.last_mut() {
+                Some(prev_layer) => {
+                    later[0].backward(prev_layer.get_output_slice(), true);
+                    prev_layer.set_deltas_from_errors(later[0].get_input_errors());
+                } None => {
+                    later[0].backward(&self.inputs, false);
+                }
+            }
+        }
+    }
+
+    fn apply_gradients(&mut self, batch_count : usize) {
+        for layer in self.layers.iter_mut() {
+            layer.apply_gradients(batch_count);
+        }
+    }
+
+    fn schedule_learning_rates(&mut self, round : usize) {
+        for layer in self.layers.iter_mut() {
+            layer.schedule_learning_rate(round);
+        }
+    }
+
+    fn record_loss(&mut self, loss : f64) {
+        for layer in self.layers.iter_mut() {
+            layer.record_loss(loss);
+        }
+    }
+
+    fn get_learning_rates(&self) -> Vec<f64> {
+        let mut learning_rates : Vec<f64> = Vec::new();
+        for layer in &self.layers {
+            learning_rates.push(layer.get_learning_rate());
+        }
+        learning_rates
+    }
+
+    fn get_settings(&self) -> Vec<LayerSettings> {
+        let mut layer_settings : Vec<LayerSettings> = Vec::new();
+        for layer in &self.layers {
+            layer_settings.push(layer.get_settings());
+        }
+        layer_settings
+    }
+
+    fn update_state(&mut self, settings : &[LayerSettings], activations : Vec<Arc<dyn Activation>>, input_size : usize) -> Result<(), TinmanError> {
+        let mut input_size : usize = input_size;
+        for (i, activation) in activations.into_iter().enumerate() {
+            self.layers[i].update_state(&settings[i], activation, input_size, i)?;
+            input_size = settings[i].output_units;
+        }
+        Ok(())
+    }
+
+    fn clip_gradients(&mut self, limit : f64) {
+        for layer in self.layers.iter_mut() {
+            layer.clip_gradients(limit);
+        }
+    }
+
+    // the length of every layer's gradients taken as one vector
+    fn get_gradient_norm(&self) -> f64 {
+        let mut squared_sum : f64 = 0.0;
+        for layer in &self.layers {
+            squared_sum += layer.get_gradient_squared_sum();
+        }
+        squared_sum.sqrt()
+    }
+
+    fn scale_gradients(&mut self, scale : f64) {
+        for layer in self.layers.iter_mut() {
+            layer.scale_gradients(scale);
+        }
+    }
+
+    fn has_finite_weights(&self) -> bool {
+        self.layers.iter().all(|layer| layer.has_finite_weights())
+    }
+
+    // every layer is checked, not just the output, an activation can hide a NaN below it
+    fn has_finite_outputs(&self) -> bool {
+        self.layers.iter().all(|layer| layer.has_finite_outputs())
+    }
+
+    fn get_learned_gradients(&self) -> Vec<Vec<f64>> {
+        self.layers.iter().map(|layer| layer.get_learned_gradients()).collect()
+    }
+
+    fn set_learned_gradients(&mut self, gradients : &[Vec<f64>]) {
+        for (layer, layer_gradients) in self.layers.iter_mut().zip(gradients) {
+            layer.set_learned_gradients(layer_gradients);
+        }
+    }
+
+    fn get_learned_value(&self, layer : usize, index : usize) -> f64 {
+        self.layers[layer].get_learned_value(index)
+    }
+
+    fn set_learned_value(&mut self, layer : usize, index : usize, value : f64) {
+        self.layers[layer].set_learned_value(index, value);
+    }
+
+    fn get_layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    fn get_precision(&self) -> &'static str {
+        F::precision()
+    }
+}
@@ -0,0 +1,87 @@
+use super::super::data_and_config::neural_net_config_parts::{LayerSettings, DENSE, BATCH_NORM};
+use super::super::tinman_error::TinmanError;
+use super::weight_init::init_layer_weights;
+use rand::StdRng;
+
+// The layer's weights from its settings, or new ones from its init strategy if it has none yet.
+pub fn make_layer_weights(
+    layer_settings : &LayerSettings,
+    input_size : usize,
+    layer : usize,
+    rng : &mut StdRng
+) -> Result<Vec<Vec<f64>>, TinmanError>
+{
+    match &layer_settings.layer_weights {
+        Some(weight_vectors) => {
+            check_layer_weights(weight_vectors, layer_settings, input_size, layer)?;
+            Ok(weight_vectors.clone())
+        } None => {
+            init_layer_weights(layer_settings, input_size, layer, rng)
+        }
+    }
+}
+
+// One weight vector per unit, each as long as the layer's weights per node.
+pub fn check_layer_weights(
+    weight_vectors : &[Vec<f64>],
+    layer_settings : &LayerSettings,
+    input_size : usize,
+    layer : usize
+) -> Result<(), TinmanError>
+{
+    if weight_vectors.len() != layer_settings.output_units {
+        return Err(TinmanError::WeightVectorCountMismatch {
+            layer,
+            expected : layer_settings.output_units,
+            actual : weight_vectors.len()
+        });
+    }
+    let weights_per_node : usize = get_weights_per_node(layer_settings, input_size, layer)?;
+    for (node, weights) in weight_vectors.iter().enumerate() {
+        if weights.len() != weights_per_node {
+            return Err(TinmanError::WeightLengthMismatch {
+                layer,
+                node,
+                expected : weights_per_node,
+                actual : weights.len()
+            });
+        }
+    }
+    Ok(())
+}
+
+// Per node biases from the settings, or the layer wide starting bias if there are none yet.
+pub fn get_layer_biases(layer_settings : &LayerSettings, layer : usize) -> Result<Vec<f64>, TinmanError> {
+    match &layer_settings.layer_biases {
+        Some(biases) => {
+            if biases.len() != layer_settings.output_units {
+                return Err(TinmanError::BiasCountMismatch {
+                    layer,
+                    expected : layer_settings.output_units,
+                    actual : biases.len()
+                });
+            }
+            Ok(biases.clone())
+        } None => {
+            Ok(vec![layer_settings.bias; layer_settings.output_units])
+        }
+    }
+}
+
+// A dense node has a weight per input, a batch norm node only has its gamma.
+pub fn get_weights_per_node(layer_settings : &LayerSettings, input_size : usize, layer : usize) -> Result<usize, TinmanError> {
+    match layer_settings.get_layer_type() {
+        DENSE => Ok(input_size),
+        BATCH_NORM => {
+            if layer_settings.output_units != input_size {
+                return Err(TinmanError::BatchNormSizeMismatch {
+                    layer,
+                    expected : input_size,
+                    actual : layer_settings.output_units
+                });
+            }
+            Ok(1)
+        }
+        layer_type => Err(TinmanError::UnknownLayerType { layer, name : layer_type.to_string() })
+    }
+}
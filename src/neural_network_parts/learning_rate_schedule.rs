@@ -0,0 +1,157 @@
+use super::super::data_and_config::neural_net_config_parts::{LayerSettings, ScheduleSettings};
+use super::super::tinman_error::TinmanError;
+
+use std::f64::consts::PI;
+
+pub const SCHEDULE_NAMES : [&str; 6] = [
+    "constant",
+    "step_decay",
+    "exponential_decay",
+    "cosine_restarts",
+    "linear_warmup",
+    "reduce_on_plateau"
+];
+
+const DEFAULT_DECAY_RATE : f64 = 0.5;
+const DEFAULT_STEP_ROUNDS : usize = 10;
+const DEFAULT_PATIENCE : usize = 3;
+const DEFAULT_CYCLE_MULTIPLIER : f64 = 1.0;
+
+// Works out a layer's learning rate for each training round from the rate in its settings.
+// reduce_on_plateau is the only schedule with state, it lowers the rate when the
+// test loss passed to record_loss stops improving.
+pub struct LearningRateSchedule {
+    schedule_code : usize,
+    schedule_fns : [fn(&LearningRateSchedule, usize) -> f64; 6],
+    settings : Option<ScheduleSettings>,
+    base_learning_rate : f64,
+    decay_rate : f64,
+    step_rounds : usize,
+    min_learning_rate : f64,
+    patience : usize,
+    cycle_multiplier : f64,
+    plateau_learning_rate : f64,
+    best_loss : f64,
+    rounds_without_improvement : usize
+}
+
+impl LearningRateSchedule {
+    pub fn new(settings : &LayerSettings, layer : usize) -> Result<LearningRateSchedule, TinmanError> {
+        let mut schedule = LearningRateSchedule {
+            schedule_code : 0,
+            schedule_fns : [
+                constant,
+                step_decay,
+                exponential_decay,
+                cosine_restarts,
+                linear_warmup,
+                reduce_on_plateau
+            ],
+            settings : None,
+            base_learning_rate : 0.0,
+            decay_rate : DEFAULT_DECAY_RATE,
+            step_rounds : DEFAULT_STEP_ROUNDS,
+            min_learning_rate : 0.0,
+            patience : DEFAULT_PATIENCE,
+            cycle_multiplier : DEFAULT_CYCLE_MULTIPLIER,
+            plateau_learning_rate : 0.0,
+            best_loss : f64::INFINITY,
+            rounds_without_improvement : 0
+        };
+        schedule.update_state(settings, layer)?;
+        Ok(schedule)
+    }
+
+    pub fn update_state(&mut self, settings : &LayerSettings, layer : usize) -> Result<(), TinmanError> {
+        self.base_learning_rate = settings.learning_rate;
+        self.settings = settings.learning_rate_schedule.clone();
+        match &settings.learning_rate_schedule {
+            Some(schedule_settings) => {
+                self.schedule_code = get_schedule_code_by_name(&schedule_settings.schedule).ok_or_else(|| {
+                    TinmanError::UnknownSchedule { layer, name : schedule_settings.schedule.clone() }
+                })?;
+                self.decay_rate = schedule_settings.decay_rate.unwrap_or(DEFAULT_DECAY_RATE);
+                self.step_rounds = schedule_settings.step_rounds.unwrap_or(DEFAULT_STEP_ROUNDS).max(1);
+                self.min_learning_rate = schedule_settings.min_learning_rate.unwrap_or(0.0);
+                self.patience = schedule_settings.patience.unwrap_or(DEFAULT_PATIENCE);
+                self.cycle_multiplier = schedule_settings.cycle_multiplier.unwrap_or(DEFAULT_CYCLE_MULTIPLIER).max(1.0);
+            } None => {
+                self.schedule_code = 0;
+            }
+        }
+        self.plateau_learning_rate = self.base_learning_rate;
+        self.best_loss = f64::INFINITY;
+        self.rounds_without_improvement = 0;
+        Ok(())
+    }
+
+    // round counts from 0
+    pub fn learning_rate_for_round(&self, round : usize) -> f64 {
+        (self.schedule_fns)[self.schedule_code](self, round)
+    }
+
+    pub fn record_loss(&mut self, loss : f64) {
+        if loss < self.best_loss {
+            self.best_loss = loss;
+            self.rounds_without_improvement = 0;
+            return;
+        }
+        self.rounds_without_improvement += 1;
+        if self.rounds_without_improvement > self.patience {
+            self.plateau_learning_rate = (self.plateau_learning_rate * self.decay_rate).max(self.min_learning_rate);
+            self.rounds_without_improvement = 0;
+        }
+    }
+
+    pub fn get_base_learning_rate(&self) -> f64 {
+        self.base_learning_rate
+    }
+
+    pub fn get_settings(&self) -> Option<ScheduleSettings> {
+        self.settings.clone()
+    }
+}
+
+pub fn get_schedule_code_by_name(schedule_name : &str) -> Option<usize> {
+    SCHEDULE_NAMES.iter().position(|name| *name == schedule_name)
+}
+
+fn constant(schedule : &LearningRateSchedule, _round : usize) -> f64 {
+    schedule.base_learning_rate
+}
+
+// multiplies the rate by decay_rate every step_rounds rounds
+fn step_decay(schedule : &LearningRateSchedule, round : usize) -> f64 {
+    let steps : i32 = (round / schedule.step_rounds) as i32;
+    (schedule.base_learning_rate * schedule.decay_rate.powi(steps)).max(schedule.min_learning_rate)
+}
+
+fn exponential_decay(schedule : &LearningRateSchedule, round : usize) -> f64 {
+    (schedule.base_learning_rate * schedule.decay_rate.powi(round as i32)).max(schedule.min_learning_rate)
+}
+
+// Anneals from the base rate down to min_learning_rate over a cycle of step_rounds rounds,
+// then jumps back up. Each cycle is cycle_multiplier times longer than the one before.
+fn cosine_restarts(schedule : &LearningRateSchedule, round : usize) -> f64 {
+    let mut cycle_length : f64 = schedule.step_rounds as f64;
+    let mut cycle_round : f64 = round as f64;
+    while cycle_round >= cycle_length {
+        cycle_round -= cycle_length;
+        cycle_length *= schedule.cycle_multiplier;
+    }
+    let cosine : f64 = (PI * cycle_round / cycle_length).cos();
+    let rate_range : f64 = schedule.base_learning_rate - schedule.min_learning_rate;
+    schedule.min_learning_rate + 0.5 * rate_range * (1.0 + cosine)
+}
+
+// ramps up to the base rate over the first step_rounds rounds
+fn linear_warmup(schedule : &LearningRateSchedule, round : usize) -> f64 {
+    if round >= schedule.step_rounds {
+        return schedule.base_learning_rate
+    }
+    schedule.base_learning_rate * (round + 1) as f64 / schedule.step_rounds as f64
+}
+
+fn reduce_on_plateau(schedule : &LearningRateSchedule, _round : usize) -> f64 {
+    schedule.plateau_learning_rate
+}
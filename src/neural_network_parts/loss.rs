@@ -0,0 +1,179 @@
+use super::activator::SOFTMAX;
+use super::super::data_and_config::neural_net_config_parts::MULTI_LABEL;
+use super::super::tinman_error::TinmanError;
+
+pub const MSE : &str = "mse";
+pub const CROSS_ENTROPY : &str = "cross_entropy";
+pub const BINARY_CROSS_ENTROPY : &str = "binary_cross_entropy";
+
+// keeps ln away from 0 when an output saturates
+const EPSILON : f64 = 1e-12;
+const HUBER_DELTA : f64 = 1.0;
+
+// Loss functions take (output, target) for a single output unit,
+// the loss of a whole row is the sum over the output layer.
+pub struct Loss {
+    fn_names : [String; 6],
+    loss_fn_code : usize,
+    loss_fns : [fn(f64, f64) -> f64; 6],
+    loss_prime_fns : [fn(f64, f64) -> f64; 6]
+}
+
+impl Default for Loss {
+    fn default() -> Loss {
+        Loss::new()
+    }
+}
+
+impl Loss {
+    pub fn new () -> Loss {
+        Loss {
+            loss_fn_code : 0,
+            fn_names : [
+                MSE.to_string(),
+                CROSS_ENTROPY.to_string(),
+                BINARY_CROSS_ENTROPY.to_string(),
+                "hinge".to_string(),
+                "huber".to_string(),
+                "mae".to_string()
+            ],
+            loss_fns : [
+                mse,
+                cross_entropy,
+                binary_cross_entropy,
+                hinge,
+                huber,
+                mae
+            ],
+            loss_prime_fns : [
+                mse_prime,
+                cross_entropy_prime,
+                binary_cross_entropy_prime,
+                hinge_prime,
+                huber_prime,
+                mae_prime
+            ]
+        }
+    }
+
+    pub fn loss (&self, output : f64, target : f64) -> f64 {
+        (self.loss_fns)[self.loss_fn_code](output, target)
+    }
+
+    pub fn loss_prime (&self, output : f64, target : f64) -> f64 {
+        (self.loss_prime_fns)[self.loss_fn_code](output, target)
+    }
+
+    // the code is only ever set from a name found in the list, so it always indexes the functions
+    pub fn set_fn_by_name (&mut self, fn_name : &str) -> Result<(), TinmanError> {
+        self.loss_fn_code = self.get_fn_code_by_name(fn_name).ok_or_else(|| TinmanError::UnknownLoss {
+            name : fn_name.to_string()
+        })?;
+        Ok(())
+    }
+
+    pub fn get_fn_code (&self) -> usize {
+        self.loss_fn_code
+    }
+
+    pub fn is_cross_entropy (&self) -> bool {
+        self.fn_names[self.loss_fn_code] == CROSS_ENTROPY
+    }
+
+    pub fn is_binary_cross_entropy (&self) -> bool {
+        self.fn_names[self.loss_fn_code] == BINARY_CROSS_ENTROPY
+    }
+
+    pub fn get_fn_code_by_name (&self, fn_name : &str) -> Option<usize> {
+        self.fn_names.iter().position(|name| name == fn_name)
+    }
+
+    pub fn get_fn_name (&self) -> String {
+        self.fn_names[self.loss_fn_code].clone()
+    }
+}
+
+// Settings written before losses were selectable have no loss,
+// they get what the output layer used to train with.
+// Multi-label networks score every output unit on its own.
+pub fn get_loss_name(loss : &Option<String>, output_activation : &str, task : &str) -> String {
+    match loss {
+        Some(name) => name.clone(),
+        None => {
+            if task == MULTI_LABEL {
+                BINARY_CROSS_ENTROPY.to_string()
+            } else if output_activation == SOFTMAX {
+                CROSS_ENTROPY.to_string()
+            } else {
+                MSE.to_string()
+            }
+        }
+    }
+}
+
+// half the squared error, so the gradient is just output - target
+fn mse(output : f64, target : f64) -> f64 {
+    0.5 * (output - target).powi(2)
+}
+
+fn mse_prime(output : f64, target : f64) -> f64 {
+    output - target
+}
+
+fn cross_entropy(output : f64, target : f64) -> f64 {
+    -target * output.max(EPSILON).ln()
+}
+
+fn cross_entropy_prime(output : f64, target : f64) -> f64 {
+    -target / output.max(EPSILON)
+}
+
+fn binary_cross_entropy(output : f64, target : f64) -> f64 {
+    let clamped : f64 = output.clamp(EPSILON, 1.0 - EPSILON);
+    -(target * clamped.ln() + (1.0 - target) * (1.0 - clamped).ln())
+}
+
+fn binary_cross_entropy_prime(output : f64, target : f64) -> f64 {
+    let clamped : f64 = output.clamp(EPSILON, 1.0 - EPSILON);
+    (clamped - target) / (clamped * (1.0 - clamped))
+}
+
+// targets are 0 or 1, hinge wants them as -1 or 1
+fn hinge(output : f64, target : f64) -> f64 {
+    let sign : f64 = 2.0 * target - 1.0;
+    (1.0 - sign * output).max(0.0)
+}
+
+fn hinge_prime(output : f64, target : f64) -> f64 {
+    let sign : f64 = 2.0 * target - 1.0;
+    if sign * output < 1.0 {
+        return -sign
+    }
+    0.0
+}
+
+fn huber(output : f64, target : f64) -> f64 {
+    let error : f64 = output - target;
+    if error.abs() <= HUBER_DELTA {
+        return 0.5 * error.powi(2)
+    }
+    HUBER_DELTA * (error.abs() - 0.5 * HUBER_DELTA)
+}
+
+fn huber_prime(output : f64, target : f64) -> f64 {
+    let error : f64 = output - target;
+    error.clamp(-HUBER_DELTA, HUBER_DELTA)
+}
+
+fn mae(output : f64, target : f64) -> f64 {
+    (output - target).abs()
+}
+
+fn mae_prime(output : f64, target : f64) -> f64 {
+    if output > target {
+        return 1.0
+    } else if output < target {
+        return -1.0
+    }
+    0.0
+}
@@ -0,0 +1,77 @@
+// A layer keeps its weights as one row-major matrix, a row per unit and a column per input.
+// These are the only loops over it the forward and backward passes need.
+
+use super::float::Float;
+
+// sums[j] = biases[j] + weights[j] . inputs
+pub fn mat_vec<F : Float>(weights : &[F], biases : &[F], inputs : &[F], sums : &mut [F]) {
+    let columns : usize = inputs.len();
+    for (j, sum) in sums.iter_mut().enumerate() {
+        let mut result : F = biases[j];
+        for (weight, input) in weights[j * columns .. (j + 1) * columns].iter().zip(inputs) {
+            result += *weight * *input;
+        }
+        *sum = result;
+    }
+}
+
+// Rows handled together by mat_mat, each row of weights is read once per block instead of once per row.
+const ROW_BLOCK : usize = 4;
+
+// mat_vec for many input rows at once, inputs and sums hold one row after another.
+// Each sum is still added up in the same order as mat_vec, so both give the same result.
+pub fn mat_mat<F : Float>(weights : &[F], biases : &[F], inputs : &[F], columns : usize, sums : &mut [F]) {
+    let units : usize = biases.len();
+    if units == 0 {
+        return;
+    }
+    let rows : usize = sums.len() / units;
+    let mut block_start : usize = 0;
+    while block_start + ROW_BLOCK <= rows {
+        let x0 : &[F] = &inputs[block_start * columns .. (block_start + 1) * columns];
+        let x1 : &[F] = &inputs[(block_start + 1) * columns .. (block_start + 2) * columns];
+        let x2 : &[F] = &inputs[(block_start + 2) * columns .. (block_start + 3) * columns];
+        let x3 : &[F] = &inputs[(block_start + 3) * columns .. (block_start + 4) * columns];
+        for j in 0 .. units {
+            let row : &[F] = &weights[j * columns .. (j + 1) * columns];
+            let mut results : [F; ROW_BLOCK] = [biases[j]; ROW_BLOCK];
+            for k in 0 .. columns {
+                let weight : F = row[k];
+                results[0] += weight * x0[k];
+                results[1] += weight * x1[k];
+                results[2] += weight * x2[k];
+                results[3] += weight * x3[k];
+            }
+            for (r, result) in results.iter().enumerate() {
+                sums[(block_start + r) * units + j] = *result;
+            }
+        }
+        block_start += ROW_BLOCK;
+    }
+    for r in block_start .. rows {
+        mat_vec(weights, biases, &inputs[r * columns .. (r + 1) * columns], &mut sums[r * units .. (r + 1) * units]);
+    }
+}
+
+// errors[k] = sum over j of weights[j][k] * deltas[j], walking the matrix row by row
+pub fn transposed_mat_vec<F : Float>(weights : &[F], deltas : &[F], errors : &mut [F]) {
+    let columns : usize = errors.len();
+    for error in errors.iter_mut() {
+        *error = F::from_f64(0.0);
+    }
+    for (j, delta) in deltas.iter().enumerate() {
+        for (error, weight) in errors.iter_mut().zip(&weights[j * columns .. (j + 1) * columns]) {
+            *error += *delta * *weight;
+        }
+    }
+}
+
+// gradients[j][k] += deltas[j] * inputs[k]
+pub fn add_outer_product<F : Float>(gradients : &mut [F], deltas : &[F], inputs : &[F]) {
+    let columns : usize = inputs.len();
+    for (j, delta) in deltas.iter().enumerate() {
+        for (gradient, input) in gradients[j * columns .. (j + 1) * columns].iter_mut().zip(inputs) {
+            *gradient += *input * *delta;
+        }
+    }
+}
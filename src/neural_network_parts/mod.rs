@@ -1,6 +1,12 @@
-pub mod node;
 pub mod activator;
 pub mod layer;
+pub mod layer_stack;
+pub mod matrix;
+pub mod float;
 pub mod neural_network;
-pub mod make_new_nodes;
-pub mod nnet_errors;
\ No newline at end of file
+pub mod layer_weights;
+pub mod loss;
+pub mod optimizer;
+pub mod learning_rate_schedule;
+pub mod weight_init;
+pub mod random;
\ No newline at end of file
@@ -1,139 +1,593 @@
-use super::activator::Activator;
-use super::node::Node;
-use super::layer::Layer;
-use super::make_new_nodes::make_new_nodes;
-use super::nnet_errors::failed_prediction;
+use super::activator::{Activation, get_activation_by_name, derivative_error, SOFTMAX};
+use super::layer_stack::{Layers, make_layers, check_precision};
+use super::loss::{Loss, get_loss_name};
+use super::random::{make_rng, seeded_rng};
+use super::super::tinman_error::TinmanError;
 use super::super::data_and_config::neural_net_config_parts::{
     NeuralNetSettings,
-    LayerSettings
+    LayerSettings,
+    CLASSIFICATION,
+    REGRESSION,
+    MULTI_LABEL
 };
+use super::super::data_and_config::model_file::{
+    save_json,
+    load_json,
+    save_binary,
+    load_binary,
+    NEURAL_NETWORK_KIND
+};
+
+use rand::StdRng;
+use std::path::Path;
+use std::sync::Arc;
+
+// rows forward_batch sends through the layers together
+const BATCH_CHUNK_ROWS : usize = 256;
+
+// How far backpropagation is from finite differences in one layer, the worst value in it.
+// The plain relative error blows up for gradients near 0, where rounding in the differences
+// is as large as the gradient itself, so max_error is the one to hold to a threshold.
+#[derive(Clone, Copy, Debug)]
+pub struct GradientError {
+    // |analytical - numerical| / max(|analytical|, |numerical|)
+    pub max_relative_error : f64,
+    // relative for gradients above 1 and absolute below
+    pub max_error : f64
+}
 
 pub struct NeuralNetwork {
-    activator : Activator,
+    loss : Loss,
+    task : String,
+    label_threshold : f64,
     query_id : usize,
     config_id : usize,
     input_size : usize,
-    layers : Vec<Layer>,
-    nodes : Vec<Node>
+    layers : Box<dyn Layers>,
+    accumulated_rows : usize,
+    training : bool,
+    seed : Option<u64>,
+    rng : StdRng
 }
 
 impl NeuralNetwork {
-    pub fn new(nnet_settings : &NeuralNetSettings) -> NeuralNetwork {
-        let mut activator = Activator::new();
-        let mut input_size : usize = nnet_settings.input_size;
-        let mut layers : Vec<Layer> = Vec::new();
-        let mut nodes : Vec<Node> = Vec::new();
-        for i in 0 .. nnet_settings.layers.len() {
-            let act_fn_name : &String = &nnet_settings.layers[i].activation_function;
-            let act_fn_code : usize = activator.get_fn_code_by_name(&act_fn_name);
-            let start_index : usize = nodes.len();
-            if i == 0 {
-                layers.push(
-                    Layer::new(
-                        &nnet_settings.layers[i], 
-                        0, 
-                        act_fn_code, 
-                        start_index
-                    )
-                );
-            } else {
-                layers.push(
-                    Layer::new(
-                        &nnet_settings.layers[i], 
-                        input_size, 
-                        act_fn_code, 
-                        start_index
-                    )
-                );
-            }
-            make_new_nodes(
-                &nnet_settings.layers[i], 
-                &mut nodes, 
-                input_size
-            );
-            input_size = nnet_settings.layers[i].output_units;
+    pub fn new(nnet_settings : &NeuralNetSettings) -> Result<NeuralNetwork, TinmanError> {
+        if nnet_settings.layers.is_empty() {
+            return Err(TinmanError::NoLayers);
         }
+        check_task(nnet_settings)?;
+        check_precision(nnet_settings)?;
+        check_softmax_layers(nnet_settings)?;
+        let mut rng : StdRng = make_rng(nnet_settings.seed);
+        let layers : Box<dyn Layers> = make_layers(nnet_settings, &mut rng)?;
+
+        let loss : Loss = make_loss(nnet_settings)?;
 
-        NeuralNetwork {
-            activator: activator,
+        Ok(NeuralNetwork {
+            loss,
+            task : nnet_settings.get_task().to_string(),
+            label_threshold : get_label_threshold(nnet_settings),
             query_id : nnet_settings.query_id,
             config_id : nnet_settings.config_id,
-            input_size : input_size,
-            layers : layers,
-            nodes : nodes
-        }
+            input_size : nnet_settings.input_size,
+            layers,
+            accumulated_rows : 0,
+            training : false,
+            seed : nnet_settings.seed,
+            rng
+        })
     }
 
     pub fn get_settings(&mut self) -> NeuralNetSettings {
-        let mut layer_settings : Vec<LayerSettings> = Vec::new();
-        for i in 0 .. self.layers.len() {
-            continue;
-        }
-        let mut settings : NeuralNetSettings = NeuralNetSettings {
+        let layer_settings : Vec<LayerSettings> = self.layers.get_settings();
+        NeuralNetSettings {
             query_id : self.query_id,
             config_id : self.config_id,
             input_size : self.input_size,
+            loss : Some(self.loss.get_fn_name()),
+            task : Some(self.task.clone()),
+            label_threshold : Some(self.label_threshold),
+            seed : self.seed,
+            precision : Some(self.layers.get_precision().to_string()),
             accuracy : 0.0,
             layers : layer_settings
-        };
-        settings
+        }
+    }
+
+    pub fn save<P : AsRef<Path>>(&mut self, path : P) -> Result<(), TinmanError> {
+        save_json(path, NEURAL_NETWORK_KIND, &self.get_settings())?;
+        Ok(())
+    }
+
+    pub fn load<P : AsRef<Path>>(path : P) -> Result<NeuralNetwork, TinmanError> {
+        let settings : NeuralNetSettings = load_json(path, NEURAL_NETWORK_KIND)?;
+        NeuralNetwork::new(&settings)
+    }
+
+    // Same as save, but layer weights are written as raw little-endian f64s.
+    pub fn save_binary<P : AsRef<Path>>(&mut self, path : P) -> Result<(), TinmanError> {
+        save_binary(path, NEURAL_NETWORK_KIND, self.get_settings(), |settings| settings)?;
+        Ok(())
+    }
+
+    pub fn load_binary<P : AsRef<Path>>(path : P) -> Result<NeuralNetwork, TinmanError> {
+        let settings : NeuralNetSettings = load_binary(path, NEURAL_NETWORK_KIND, |settings| settings)?;
+        NeuralNetwork::new(&settings)
+    }
+
+    // Dropout is used when the network is in training mode.
+    pub fn forward(&mut self, inputs : &[f64]) -> Result<(), TinmanError> {
+        let training : bool = self.training;
+        self.forward_in_mode(inputs, training)
+    }
+
+    fn forward_in_mode(&mut self, inputs : &[f64], training : bool) -> Result<(), TinmanError> {
+        self.check_inputs(inputs)?;
+        self.layers.forward(inputs, 1, training, &mut self.rng);
+        Ok(())
+    }
+
+    // Runs the rows of a batch through the network together, as forward does for one row.
+    // In training mode batch norm layers normalize with the statistics of these rows.
+    // The rows' deltas and gradients then go through set_error_deltas and accumulate_gradients_for_rows.
+    pub fn forward_rows<R : AsRef<[f64]>>(&mut self, rows : &[R]) -> Result<(), TinmanError> {
+        for (i, row) in rows.iter().enumerate() {
+            self.check_inputs(row.as_ref()).map_err(|error| error.at_row(i))?;
+        }
+        let training : bool = self.training;
+        self.layers.forward(&join_rows(rows), rows.len(), training, &mut self.rng);
+        Ok(())
+    }
+
+    // Runs many rows through the network in inference mode, a layer at a time as matrix
+    // products, and returns the outputs of the last layer for each row.
+    // Rows are taken a chunk at a time so memory stays bounded for large tables.
+    pub fn forward_batch<R : AsRef<[f64]>>(&mut self, rows : &[R]) -> Result<Vec<Vec<f64>>, TinmanError> {
+        for (i, row) in rows.iter().enumerate() {
+            self.check_inputs(row.as_ref()).map_err(|error| error.at_row(i))?;
+        }
+        let output_units : usize = self.get_output_units();
+        let mut outputs : Vec<Vec<f64>> = Vec::with_capacity(rows.len());
+        for chunk in rows.chunks(BATCH_CHUNK_ROWS) {
+            let values : Vec<f64> = self.layers.forward_batch(&join_rows(chunk), chunk.len());
+            for row_outputs in values.chunks_exact(output_units.max(1)) {
+                outputs.push(row_outputs.to_vec());
+            }
+        }
+        Ok(outputs)
+    }
+
+    pub fn check_inputs(&self, inputs : &[f64]) -> Result<(), TinmanError> {
+        if inputs.len() != self.input_size {
+            return Err(TinmanError::InputSizeMismatch { row : None, expected : self.input_size, actual : inputs.len() });
+        }
+        Ok(())
+    }
+
+    // Training mode turns on dropout and batch norm statistics for forward and forward_rows,
+    // predictions always run without them.
+    pub fn set_training(&mut self, training : bool) {
+        self.training = training;
+    }
+
+    pub fn is_training(&self) -> bool {
+        self.training
+    }
+
+    // restarts the dropout masks from a new seed
+    pub fn reseed(&mut self, seed : u64) {
+        self.rng = seeded_rng(seed);
+    }
+
+    pub fn set_error_delta(&mut self, correct_index : usize) -> Result<(), TinmanError> {
+        self.check_labels(&[correct_index])?;
+        let targets : Vec<f64> = self.one_hot_targets(correct_index);
+        self.set_error_delta_for_targets(&targets)
+    }
+
+    pub fn set_error_delta_for_targets(&mut self, targets : &[f64]) -> Result<(), TinmanError> {
+        self.check_row_count(1)?;
+        self.check_targets(targets)?;
+        self.layers.set_delta(targets, &self.loss);
+        Ok(())
+    }
+
+    // targets for each row of the last forward_rows, in the same order
+    pub fn set_error_deltas<T : AsRef<[f64]>>(&mut self, targets : &[T]) -> Result<(), TinmanError> {
+        self.check_row_count(targets.len())?;
+        for (i, row_targets) in targets.iter().enumerate() {
+            self.check_targets(row_targets.as_ref()).map_err(|error| error.at_row(i))?;
+        }
+        self.layers.set_delta(&join_rows(targets), &self.loss);
+        Ok(())
+    }
+
+    fn check_row_count(&self, row_count : usize) -> Result<(), TinmanError> {
+        let expected : usize = self.layers.get_row_count();
+        if row_count != expected {
+            return Err(TinmanError::RowCountMismatch { expected, actual : row_count });
+        }
+        Ok(())
+    }
+
+    // loss of the outputs left by the last forward pass
+    pub fn get_loss(&self, correct_index : usize) -> f64 {
+        let targets : Vec<f64> = self.one_hot_targets(correct_index);
+        self.get_loss_for_targets(&targets)
+    }
+
+    pub fn set_error_delta_for_labels(&mut self, labels : &[usize]) -> Result<(), TinmanError> {
+        self.check_labels(labels)?;
+        let targets : Vec<f64> = self.multi_hot_targets(labels);
+        self.set_error_delta_for_targets(&targets)
+    }
+
+    pub fn check_targets(&self, targets : &[f64]) -> Result<(), TinmanError> {
+        let output_units : usize = self.get_output_units();
+        if targets.len() != output_units {
+            return Err(TinmanError::TargetSizeMismatch { row : None, expected : output_units, actual : targets.len() });
+        }
+        Ok(())
+    }
+
+    pub fn check_labels(&self, labels : &[usize]) -> Result<(), TinmanError> {
+        let output_units : usize = self.get_output_units();
+        for label in labels {
+            if *label >= output_units {
+                return Err(TinmanError::LabelOutOfRange { row : None, label : *label, output_units });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_loss_for_labels(&self, labels : &[usize]) -> f64 {
+        let targets : Vec<f64> = self.multi_hot_targets(labels);
+        self.get_loss_for_targets(&targets)
+    }
+
+    pub fn get_loss_for_targets(&self, targets : &[f64]) -> f64 {
+        self.layers.get_loss(targets, &self.loss)
+    }
+
+    // summed loss of the rows of the last forward_rows
+    pub fn get_loss_for_rows<T : AsRef<[f64]>>(&self, targets : &[T]) -> f64 {
+        self.layers.get_loss(&join_rows(targets), &self.loss)
+    }
+
+    // loss of outputs kept from elsewhere, such as a row of forward_batch
+    pub fn get_loss_for_outputs(&self, outputs : &[f64], targets : &[f64]) -> f64 {
+        let mut total : f64 = 0.0;
+        for (output, target) in outputs.iter().zip(targets) {
+            total += self.loss.loss(*output, *target);
+        }
+        total
+    }
+
+    fn one_hot_targets(&self, correct_index : usize) -> Vec<f64> {
+        self.multi_hot_targets(&[correct_index])
+    }
+
+    // one for every listed label and zero elsewhere, what the outputs are trained towards
+    pub fn multi_hot_targets(&self, labels : &[usize]) -> Vec<f64> {
+        let mut targets : Vec<f64> = vec![0.0; self.get_output_units()];
+        for label in labels {
+            if *label < targets.len() {
+                targets[*label] = 1.0;
+            }
+        }
+        targets
+    }
+
+    // backpropagates and updates the weights straight away
+    pub fn backward(&mut self, inputs : &[f64]) -> Result<(), TinmanError> {
+        self.accumulate_gradients(inputs)?;
+        self.apply_gradients();
+        Ok(())
+    }
+
+    // Backpropagates the error delta and adds the gradients of this row to the
+    // running sums, without touching any weights.
+    pub fn accumulate_gradients(&mut self, inputs : &[f64]) -> Result<(), TinmanError> {
+        self.check_row_count(1)?;
+        self.check_inputs(inputs)?;
+        self.layers.accumulate_gradients(inputs);
+        self.accumulated_rows += 1;
+        Ok(())
+    }
+
+    // accumulate_gradients for the rows of the last forward_rows
+    pub fn accumulate_gradients_for_rows<R : AsRef<[f64]>>(&mut self, rows : &[R]) -> Result<(), TinmanError> {
+        self.check_row_count(rows.len())?;
+        for (i, row) in rows.iter().enumerate() {
+            self.check_inputs(row.as_ref()).map_err(|error| error.at_row(i))?;
+        }
+        self.layers.accumulate_gradients(&join_rows(rows));
+        self.accumulated_rows += rows.len();
+        Ok(())
+    }
+
+    // true if a layer normalizes with batch statistics, which a batch of one row can't give
+    pub fn has_batch_norm(&self) -> bool {
+        self.layers.has_batch_norm()
+    }
+
+    pub fn gradient_check(&mut self, inputs : &[f64], label : usize, epsilon : f64) -> Result<Vec<GradientError>, TinmanError> {
+        self.check_labels(&[label])?;
+        let targets : Vec<f64> = self.one_hot_targets(label);
+        self.gradient_check_for_targets(inputs, &targets, epsilon)
+    }
+
+    // Compares the gradients backpropagation finds for one row with central differences
+    // of the loss, moving every weight, bias and activation parameter by epsilon in turn,
+    // and returns the largest errors in each layer. Runs in
+    // inference mode so dropout and batch norm statistics don't move the loss, and leaves
+    // the weights and any gradients accumulated for the current batch as they were.
+    // With f32 weights an epsilon much below 1e-2 is lost to rounding.
+    pub fn gradient_check_for_targets(&mut self, inputs : &[f64], targets : &[f64], epsilon : f64) -> Result<Vec<GradientError>, TinmanError> {
+        self.check_inputs(inputs)?;
+        self.check_targets(targets)?;
+        let pending_gradients : Vec<Vec<f64>> = self.layers.get_learned_gradients();
+        let zeros : Vec<Vec<f64>> = pending_gradients.iter().map(|gradients| vec![0.0; gradients.len()]).collect();
+        self.layers.set_learned_gradients(&zeros);
+        self.forward_in_mode(inputs, false)?;
+        self.layers.set_delta(targets, &self.loss);
+        self.layers.accumulate_gradients(inputs);
+        let analytical_gradients : Vec<Vec<f64>> = self.layers.get_learned_gradients();
+        self.layers.set_learned_gradients(&pending_gradients);
+
+        let mut max_errors : Vec<GradientError> = Vec::new();
+        for (layer, gradients) in analytical_gradients.iter().enumerate() {
+            let mut max_error : GradientError = GradientError { max_relative_error : 0.0, max_error : 0.0 };
+            for (index, analytical) in gradients.iter().enumerate() {
+                let value : f64 = self.layers.get_learned_value(layer, index);
+                self.layers.set_learned_value(layer, index, value + epsilon);
+                self.forward_in_mode(inputs, false)?;
+                let loss_above : f64 = self.get_loss_for_targets(targets);
+                self.layers.set_learned_value(layer, index, value - epsilon);
+                self.forward_in_mode(inputs, false)?;
+                let loss_below : f64 = self.get_loss_for_targets(targets);
+                self.layers.set_learned_value(layer, index, value);
+                let numerical : f64 = (loss_above - loss_below) / (2.0 * epsilon);
+                max_error.max_relative_error = max_error.max_relative_error.max(relative_error(*analytical, numerical));
+                max_error.max_error = max_error.max_error.max(derivative_error(*analytical, numerical));
+            }
+            max_errors.push(max_error);
+        }
+        Ok(max_errors)
     }
 
-    pub fn forward(&mut self, inputs: &Vec<f64>) {
-        self.layers[0].input_layer_forward(
-            inputs, 
-            &mut self.nodes, 
-            &mut self.activator
-        );
-        for i in 1 .. self.layers.len() {
-            self.layers[i].hidden_layer_forward(
-                &mut self.nodes, 
-                &mut self.activator
-            );
+    // Clips the mean gradient of the rows accumulated since the last update, each value
+    // to within ±clip_value and then all of them as one vector to a length of clip_norm.
+    // A limit that isn't above 0 is ignored.
+    pub fn clip_gradients(&mut self, clip_value : Option<f64>, clip_norm : Option<f64>) {
+        if self.accumulated_rows == 0 {
+            return;
+        }
+        // the layers hold sums, so the limits grow with the rows in them
+        let rows : f64 = self.accumulated_rows as f64;
+        if let Some(clip_value) = clip_value.filter(|limit| *limit > 0.0) {
+            self.layers.clip_gradients(clip_value * rows);
+        }
+        if let Some(clip_norm) = clip_norm.filter(|limit| *limit > 0.0) {
+            let norm : f64 = self.layers.get_gradient_norm();
+            if norm > clip_norm * rows {
+                self.layers.scale_gradients(clip_norm * rows / norm);
+            }
         }
     }
 
-    pub fn set_error_delta(&mut self, correct_index : usize) {
-        let last_layer_idx : usize = self.layers.len() - 1;
-        self.layers[last_layer_idx].set_delta(
-            correct_index, 
-            &mut self.nodes
-        );
+    // false once a weight, bias or activation parameter has become NaN or infinite
+    pub fn has_finite_weights(&self) -> bool {
+        self.layers.has_finite_weights()
+    }
+
+    // checks the outputs of every layer from the last forward pass
+    pub fn has_finite_activations(&self) -> bool {
+        self.layers.has_finite_outputs()
     }
 
-    pub fn backward(&mut self, inputs: &Vec<f64>) {
-        let last_layer_idx : usize = self.layers.len();
-        for i in (1 .. last_layer_idx).rev() {
-            self.layers[i].hidden_layer_backward(&mut self.nodes);
+    // Updates the weights with the mean gradient of the rows accumulated since the last update.
+    pub fn apply_gradients(&mut self) {
+        if self.accumulated_rows == 0 {
+            return;
         }
-        self.layers[0].input_layer_backward(inputs, &mut self.nodes);
+        self.layers.apply_gradients(self.accumulated_rows);
+        self.accumulated_rows = 0;
+    }
+
+    // drops the gradients accumulated since the last update without applying them
+    pub fn discard_gradients(&mut self) {
+        let zeros : Vec<Vec<f64>> = self.layers.get_learned_gradients().iter().map(|gradients| vec![0.0; gradients.len()]).collect();
+        self.layers.set_learned_gradients(&zeros);
+        self.accumulated_rows = 0;
+    }
+
+    // Sets every layer's learning rate from its schedule, round counts from 0.
+    pub fn schedule_learning_rates(&mut self, round : usize) {
+        self.layers.schedule_learning_rates(round);
+    }
+
+    // loss at the end of a round, for schedules that react to it
+    pub fn record_loss(&mut self, loss : f64) {
+        self.layers.record_loss(loss);
+    }
+
+    pub fn get_learning_rates(&self) -> Vec<f64> {
+        self.layers.get_learning_rates()
+    }
+
+    pub fn predict(&mut self, inputs : &[f64]) -> Result<usize, TinmanError> {
+        self.forward_in_mode(inputs, false)?;
+        self.get_prediction_from_outputs(&self.layers.get_outputs())
+    }
+
+    // predict for many rows at once, see forward_batch
+    pub fn predict_batch<R : AsRef<[f64]>>(&mut self, rows : &[R]) -> Result<Vec<usize>, TinmanError> {
+        let outputs : Vec<Vec<f64>> = self.forward_batch(rows)?;
+        let mut predictions : Vec<usize> = Vec::with_capacity(outputs.len());
+        for row_outputs in &outputs {
+            predictions.push(self.get_prediction_from_outputs(row_outputs)?);
+        }
+        Ok(predictions)
+    }
+
+    // index of the largest finite output
+    pub fn get_prediction_from_outputs(&self, outputs : &[f64]) -> Result<usize, TinmanError> {
+        let mut max_idx : Option<usize> = None;
+        let mut max_val : f64 = f64::NEG_INFINITY;
+        for (i, current) in outputs.iter().enumerate() {
+            if current.is_finite() && (max_idx.is_none() || *current > max_val) {
+                max_val = *current;
+                max_idx = Some(i);
+            }
+        }
+        max_idx.ok_or(TinmanError::FailedPrediction)
+    }
+
+    // raw outputs of the last layer, for regression networks
+    pub fn predict_values(&mut self, inputs : &[f64]) -> Result<Vec<f64>, TinmanError> {
+        self.forward_in_mode(inputs, false)?;
+        Ok(self.layers.get_outputs())
+    }
+
+    // every class whose output is at or above the label threshold, for multi-label networks
+    pub fn predict_labels(&mut self, inputs : &[f64]) -> Result<Vec<usize>, TinmanError> {
+        let outputs : Vec<f64> = self.predict_values(inputs)?;
+        Ok(self.get_labels_from_outputs(&outputs))
     }
 
-    pub fn predict(&mut self, inputs: &Vec<f64>) -> usize {
-        self.forward(inputs);
-        let last_layer_idx : usize = self.layers.len() - 1;
-        let prediction : i64 = self.layers[last_layer_idx].get_prediction(&mut self.nodes);
-        if prediction < 0 {
-            panic!(failed_prediction());
+    pub fn get_labels_from_outputs(&self, outputs : &[f64]) -> Vec<usize> {
+        let mut labels : Vec<usize> = Vec::new();
+        for (label, output) in outputs.iter().enumerate() {
+            if *output >= self.label_threshold {
+                labels.push(label);
+            }
         }
-        prediction as usize
+        labels
+    }
+
+    pub fn set_label_threshold(&mut self, threshold : f64) {
+        self.label_threshold = threshold;
+    }
+
+    pub fn get_label_threshold(&self) -> f64 {
+        self.label_threshold
+    }
+
+    pub fn get_output_units(&self) -> usize {
+        self.layers.get_output_units()
+    }
+
+    pub fn is_regression(&self) -> bool {
+        self.task == REGRESSION
+    }
+
+    pub fn is_multi_label(&self) -> bool {
+        self.task == MULTI_LABEL
     }
 
     pub fn get_id(&self) -> usize {
         self.config_id
     }
 
-    pub fn update_state(&mut self, current_default : &NeuralNetSettings) {
-        self.config_id = current_default.config_id;
-        self.query_id = current_default.query_id;
+    pub fn update_state(&mut self, current_default : &NeuralNetSettings) -> Result<(), TinmanError> {
         let layer_settings : &Vec<LayerSettings> = &current_default.layers;
-        if layer_settings.len() != self.layers.len() {
-            panic!("Updating settings does not match number of layers");
+        if layer_settings.len() != self.layers.get_layer_count() {
+            return Err(TinmanError::LayerCountMismatch {
+                expected : self.layers.get_layer_count(),
+                actual : layer_settings.len()
+            });
+        }
+        // the layers can't change the float type they were built with
+        if current_default.get_precision() != self.layers.get_precision() {
+            return Err(TinmanError::PrecisionMismatch {
+                expected : self.layers.get_precision().to_string(),
+                actual : current_default.get_precision().to_string()
+            });
+        }
+        // every name is looked up before anything is changed
+        check_task(current_default)?;
+        check_softmax_layers(current_default)?;
+        let loss : Loss = make_loss(current_default)?;
+        let mut activations : Vec<Arc<dyn Activation>> = Vec::new();
+        for (i, layer_setting) in layer_settings.iter().enumerate() {
+            activations.push(get_activation(layer_setting, i)?);
         }
-        for i in 0 .. self.layers.len() {
-            let layer_setting = &layer_settings[i];
-            let fn_code = self.activator.get_fn_code_by_name(&layer_setting.activation_function);
-            self.layers[i].update_state(layer_setting, fn_code, &mut self.nodes);
+        self.config_id = current_default.config_id;
+        self.query_id = current_default.query_id;
+        self.accumulated_rows = 0;
+        self.task = current_default.get_task().to_string();
+        self.label_threshold = get_label_threshold(current_default);
+        // a seeded network behaves the same whichever trainer it was recycled into
+        self.seed = current_default.seed;
+        if let Some(seed) = self.seed {
+            self.rng = seeded_rng(seed);
         }
+        self.loss = loss;
+        self.layers.update_state(layer_settings, activations, self.input_size)
+    }
+}
+
+// 0 when both are 0, where there is nothing to be relative to
+fn relative_error(analytical : f64, numerical : f64) -> f64 {
+    let scale : f64 = analytical.abs().max(numerical.abs());
+    if !analytical.is_finite() || !numerical.is_finite() {
+        f64::INFINITY
+    } else if scale == 0.0 {
+        0.0
+    } else {
+        (analytical - numerical).abs() / scale
     }
-}
\ No newline at end of file
+}
+
+// the rows one after another, how the layers take a batch
+fn join_rows<R : AsRef<[f64]>>(rows : &[R]) -> Vec<f64> {
+    let mut values : Vec<f64> = Vec::new();
+    for row in rows {
+        values.extend_from_slice(row.as_ref());
+    }
+    values
+}
+
+pub fn check_task(nnet_settings : &NeuralNetSettings) -> Result<(), TinmanError> {
+    match nnet_settings.get_task() {
+        CLASSIFICATION | REGRESSION | MULTI_LABEL => Ok(()),
+        task => Err(TinmanError::UnknownTask { name : task.to_string() })
+    }
+}
+
+// Hidden layers backpropagate through the diagonal of their activation's derivative only,
+// which is wrong for softmax, so it is kept to the output layer where the loss handles it.
+pub fn check_softmax_layers(nnet_settings : &NeuralNetSettings) -> Result<(), TinmanError> {
+    let hidden_layers : usize = nnet_settings.layers.len().saturating_sub(1);
+    match nnet_settings.layers[.. hidden_layers].iter().position(|layer_settings| layer_settings.activation_function == SOFTMAX) {
+        Some(layer) => Err(TinmanError::HiddenSoftmax { layer }),
+        None => Ok(())
+    }
+}
+
+pub fn get_activation(layer_settings : &LayerSettings, layer : usize) -> Result<Arc<dyn Activation>, TinmanError> {
+    let name : &str = &layer_settings.activation_function;
+    get_activation_by_name(name).ok_or_else(|| TinmanError::UnknownActivation {
+        layer,
+        name : name.to_string()
+    })
+}
+
+pub fn make_loss(nnet_settings : &NeuralNetSettings) -> Result<Loss, TinmanError> {
+    let mut loss : Loss = Loss::new();
+    loss.set_fn_by_name(&get_output_loss_name(nnet_settings))?;
+    Ok(loss)
+}
+
+fn get_output_loss_name(nnet_settings : &NeuralNetSettings) -> String {
+    let task : &str = nnet_settings.get_task();
+    match nnet_settings.layers.last() {
+        Some(output_layer) => get_loss_name(&nnet_settings.loss, &output_layer.activation_function, task),
+        None => get_loss_name(&nnet_settings.loss, "", task)
+    }
+}
+
+fn get_label_threshold(nnet_settings : &NeuralNetSettings) -> f64 {
+    nnet_settings.label_threshold.unwrap_or(0.5)
+}
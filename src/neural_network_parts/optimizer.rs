@@ -0,0 +1,177 @@
+use super::super::data_and_config::neural_net_config_parts::LayerSettings;
+use super::super::tinman_error::TinmanError;
+use super::float::Float;
+
+pub const MOMENTUM : &str = "momentum";
+pub const OPTIMIZER_NAMES : [&str; 5] = [
+    MOMENTUM,
+    "nesterov",
+    "adagrad",
+    "rmsprop",
+    "adam"
+];
+
+const DEFAULT_BETA1 : f64 = 0.9;
+const DEFAULT_BETA2 : f64 = 0.999;
+const DEFAULT_EPSILON : f64 = 1e-8;
+
+// a rule takes the gradients and the first and second state of every weight in a layer
+type StepFn<F> = fn(&Optimizer, &mut [F], &mut [F], &mut [F]);
+
+// Works out how far to move each weight of a layer given its gradient.
+// Every weight has two slots of state, first holds the velocity / first moment,
+// second holds the running squared gradients, the rules only use the ones they need.
+// A rule runs over the whole layer at once, so it is only looked up once per update.
+pub struct Optimizer {
+    optimizer_code : usize,
+    learning_rate : f64,
+    momentum : f64,
+    beta1 : f64,
+    beta2 : f64,
+    epsilon : f64,
+    time_step : i32
+}
+
+impl Optimizer {
+    pub fn new(settings : &LayerSettings, layer : usize) -> Result<Optimizer, TinmanError> {
+        let mut optimizer = Optimizer {
+            optimizer_code : 0,
+            learning_rate : 0.0,
+            momentum : 0.0,
+            beta1 : DEFAULT_BETA1,
+            beta2 : DEFAULT_BETA2,
+            epsilon : DEFAULT_EPSILON,
+            time_step : 0
+        };
+        optimizer.update_state(settings, layer)?;
+        Ok(optimizer)
+    }
+
+    pub fn update_state(&mut self, settings : &LayerSettings, layer : usize) -> Result<(), TinmanError> {
+        self.optimizer_code = match &settings.optimizer {
+            Some(name) => get_optimizer_code_by_name(name).ok_or_else(|| TinmanError::UnknownOptimizer {
+                layer,
+                name : name.clone()
+            })?,
+            None => 0
+        };
+        self.learning_rate = settings.learning_rate;
+        self.momentum = settings.momentum;
+        self.beta1 = settings.beta1.unwrap_or(DEFAULT_BETA1);
+        self.beta2 = settings.beta2.unwrap_or(DEFAULT_BETA2);
+        self.epsilon = settings.epsilon.unwrap_or(DEFAULT_EPSILON);
+        self.time_step = 0;
+        Ok(())
+    }
+
+    // called once per backward pass, before any step
+    pub fn next_time_step(&mut self) {
+        self.time_step += 1;
+    }
+
+    // Replaces each gradient with the amount to subtract from its weight.
+    // The rules are generic over the layer's float type, so the table is built here.
+    pub fn step<F : Float>(&self, gradients : &mut [F], firsts : &mut [F], seconds : &mut [F]) {
+        let step_fns : [StepFn<F>; 5] = [
+            momentum_step,
+            nesterov_step,
+            adagrad_step,
+            rmsprop_step,
+            adam_step
+        ];
+        step_fns[self.optimizer_code](self, gradients, firsts, seconds)
+    }
+
+    pub fn get_name(&self) -> String {
+        OPTIMIZER_NAMES[self.optimizer_code].to_string()
+    }
+
+    pub fn get_learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    pub fn set_learning_rate(&mut self, learning_rate : f64) {
+        self.learning_rate = learning_rate;
+    }
+
+    pub fn get_momentum(&self) -> f64 {
+        self.momentum
+    }
+
+    pub fn get_beta1(&self) -> f64 {
+        self.beta1
+    }
+
+    pub fn get_beta2(&self) -> f64 {
+        self.beta2
+    }
+
+    pub fn get_epsilon(&self) -> f64 {
+        self.epsilon
+    }
+}
+
+pub fn get_optimizer_code_by_name(optimizer_name : &str) -> Option<usize> {
+    OPTIMIZER_NAMES.iter().position(|name| *name == optimizer_name)
+}
+
+fn momentum_step<F : Float>(optimizer : &Optimizer, gradients : &mut [F], velocities : &mut [F], _caches : &mut [F]) {
+    let momentum : F = F::from_f64(optimizer.momentum);
+    let learning_rate : F = F::from_f64(optimizer.learning_rate);
+    for (gradient, velocity) in gradients.iter_mut().zip(velocities.iter_mut()) {
+        *velocity = momentum * *velocity + learning_rate * *gradient;
+        *gradient = *velocity;
+    }
+}
+
+// looks ahead along the velocity before applying the gradient
+fn nesterov_step<F : Float>(optimizer : &Optimizer, gradients : &mut [F], velocities : &mut [F], _caches : &mut [F]) {
+    let momentum : F = F::from_f64(optimizer.momentum);
+    let learning_rate : F = F::from_f64(optimizer.learning_rate);
+    for (gradient, velocity) in gradients.iter_mut().zip(velocities.iter_mut()) {
+        let adjusted_gradient : F = learning_rate * *gradient;
+        *velocity = momentum * *velocity + adjusted_gradient;
+        *gradient = momentum * *velocity + adjusted_gradient;
+    }
+}
+
+fn adagrad_step<F : Float>(optimizer : &Optimizer, gradients : &mut [F], _velocities : &mut [F], caches : &mut [F]) {
+    let learning_rate : F = F::from_f64(optimizer.learning_rate);
+    let epsilon : F = F::from_f64(optimizer.epsilon);
+    for (gradient, cache) in gradients.iter_mut().zip(caches.iter_mut()) {
+        *cache += *gradient * *gradient;
+        *gradient = learning_rate * *gradient / (cache.sqrt() + epsilon);
+    }
+}
+
+// beta2 is the decay rate of the squared gradient average
+fn rmsprop_step<F : Float>(optimizer : &Optimizer, gradients : &mut [F], _velocities : &mut [F], caches : &mut [F]) {
+    let learning_rate : F = F::from_f64(optimizer.learning_rate);
+    let epsilon : F = F::from_f64(optimizer.epsilon);
+    let beta2 : F = F::from_f64(optimizer.beta2);
+    let one_minus_beta2 : F = F::from_f64(1.0 - optimizer.beta2);
+    for (gradient, cache) in gradients.iter_mut().zip(caches.iter_mut()) {
+        *cache = beta2 * *cache + one_minus_beta2 * *gradient * *gradient;
+        *gradient = learning_rate * *gradient / (cache.sqrt() + epsilon);
+    }
+}
+
+fn adam_step<F : Float>(optimizer : &Optimizer, gradients : &mut [F], first_moments : &mut [F], second_moments : &mut [F]) {
+    let time_step : i32 = optimizer.time_step.max(1);
+    let first_correction : F = F::from_f64(1.0 - optimizer.beta1.powi(time_step));
+    let second_correction : F = F::from_f64(1.0 - optimizer.beta2.powi(time_step));
+    let learning_rate : F = F::from_f64(optimizer.learning_rate);
+    let epsilon : F = F::from_f64(optimizer.epsilon);
+    let beta1 : F = F::from_f64(optimizer.beta1);
+    let beta2 : F = F::from_f64(optimizer.beta2);
+    let one_minus_beta1 : F = F::from_f64(1.0 - optimizer.beta1);
+    let one_minus_beta2 : F = F::from_f64(1.0 - optimizer.beta2);
+    let moments = first_moments.iter_mut().zip(second_moments.iter_mut());
+    for (gradient, (first_moment, second_moment)) in gradients.iter_mut().zip(moments) {
+        *first_moment = beta1 * *first_moment + one_minus_beta1 * *gradient;
+        *second_moment = beta2 * *second_moment + one_minus_beta2 * *gradient * *gradient;
+        let corrected_first : F = *first_moment / first_correction;
+        let corrected_second : F = *second_moment / second_correction;
+        *gradient = learning_rate * corrected_first / (corrected_second.sqrt() + epsilon);
+    }
+}
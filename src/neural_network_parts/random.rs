@@ -0,0 +1,25 @@
+use rand::{Rng, SeedableRng, StdRng};
+
+// Every random draw in the crate comes from one of these, so a run started
+// from the same seed can be repeated exactly. Without a seed the generator is
+// seeded from the thread rng.
+pub fn make_rng(seed : Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => seeded_rng(seed),
+        None => seeded_rng(rand::thread_rng().gen::<u64>())
+    }
+}
+
+pub fn seeded_rng(seed : u64) -> StdRng {
+    let seed_parts : [usize; 2] = [seed as usize, (seed >> 32) as usize];
+    StdRng::from_seed(&seed_parts[..])
+}
+
+// Mixes a stream number into a seed (splitmix64), so parts of the crate that
+// share a seed still draw unrelated numbers.
+pub fn derive_seed(seed : u64, stream : u64) -> u64 {
+    let mut mixed : u64 = seed.wrapping_add(stream.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    mixed ^ (mixed >> 31)
+}
@@ -0,0 +1,131 @@
+use super::super::data_and_config::neural_net_config_parts::LayerSettings;
+use super::layer_weights::get_weights_per_node;
+use super::super::tinman_error::TinmanError;
+
+use rand::Rng;
+use rand::distributions::{Normal, IndependentSample};
+
+pub const UNIFORM : &str = "uniform";
+pub const INIT_NAMES : [&str; 5] = [
+    UNIFORM,
+    "xavier",
+    "he",
+    "lecun",
+    "orthogonal"
+];
+
+// Starting weights for every node of a layer, one vector per node.
+// fan in is the layer's input size and fan out its number of units,
+// layers without an init strategy draw each weight from weight_range.
+pub fn init_layer_weights<R : Rng>(
+    layer_settings : &LayerSettings,
+    input_size : usize,
+    layer : usize,
+    rng : &mut R
+) -> Result<Vec<Vec<f64>>, TinmanError>
+{
+    let weights_per_node : usize = get_weights_per_node(layer_settings, input_size, layer)?;
+    let units : usize = layer_settings.output_units;
+    if layer_settings.is_batch_norm() {
+        // gamma starts as the identity scale
+        return Ok(vec![vec![1.0; weights_per_node]; units])
+    }
+    let init_name : &str = match &layer_settings.init {
+        Some(name) => name,
+        None => UNIFORM
+    };
+    let fan_in : f64 = input_size.max(1) as f64;
+    let fan_out : f64 = units.max(1) as f64;
+    let init_code : usize = init_code_by_name(init_name).ok_or_else(|| TinmanError::UnknownInit {
+        layer,
+        name : init_name.to_string()
+    })?;
+    let weights : Vec<Vec<f64>> = match init_code {
+        0 => {
+            let low : f64 = layer_settings.weight_range[0];
+            let high : f64 = layer_settings.weight_range[1];
+            uniform_weights(units, weights_per_node, low, high, rng)
+        }
+        1 => {
+            let limit : f64 = (6.0 / (fan_in + fan_out)).sqrt();
+            uniform_weights(units, weights_per_node, -limit, limit, rng)
+        }
+        2 => normal_weights(units, weights_per_node, (2.0 / fan_in).sqrt(), rng),
+        3 => normal_weights(units, weights_per_node, (1.0 / fan_in).sqrt(), rng),
+        _ => orthogonal_weights(units, weights_per_node, rng)
+    };
+    Ok(weights)
+}
+
+pub fn init_code_by_name(init_name : &str) -> Option<usize> {
+    INIT_NAMES.iter().position(|name| *name == init_name)
+}
+
+fn uniform_weights<R : Rng>(units : usize, inputs : usize, low : f64, high : f64, rng : &mut R) -> Vec<Vec<f64>> {
+    let mut weights : Vec<Vec<f64>> = Vec::new();
+    for _ in 0 .. units {
+        let mut node_weights : Vec<f64> = Vec::new();
+        for _ in 0 .. inputs {
+            if low < high {
+                node_weights.push(rng.gen_range(low, high));
+            } else {
+                node_weights.push(low);
+            }
+        }
+        weights.push(node_weights);
+    }
+    weights
+}
+
+fn normal_weights<R : Rng>(units : usize, inputs : usize, std_dev : f64, rng : &mut R) -> Vec<Vec<f64>> {
+    let normal = Normal::new(0.0, std_dev);
+    let mut weights : Vec<Vec<f64>> = Vec::new();
+    for _ in 0 .. units {
+        let mut node_weights : Vec<f64> = Vec::new();
+        for _ in 0 .. inputs {
+            node_weights.push(normal.ind_sample(rng));
+        }
+        weights.push(node_weights);
+    }
+    weights
+}
+
+// Orthonormalizes a random normal matrix with Gram-Schmidt. When there are more
+// units than inputs the rows can't all be orthogonal, so the columns are instead.
+fn orthogonal_weights<R : Rng>(units : usize, inputs : usize, rng : &mut R) -> Vec<Vec<f64>> {
+    if units <= inputs {
+        return orthonormal_rows(units, inputs, rng)
+    }
+    let columns : Vec<Vec<f64>> = orthonormal_rows(inputs, units, rng);
+    let mut weights : Vec<Vec<f64>> = vec![vec![0.0; inputs]; units];
+    for (j, column) in columns.iter().enumerate() {
+        for (i, value) in column.iter().enumerate() {
+            weights[i][j] = *value;
+        }
+    }
+    weights
+}
+
+fn orthonormal_rows<R : Rng>(rows : usize, length : usize, rng : &mut R) -> Vec<Vec<f64>> {
+    let mut matrix : Vec<Vec<f64>> = normal_weights(rows, length, 1.0, rng);
+    for i in 0 .. rows {
+        for j in 0 .. i {
+            let projection : f64 = dot(&matrix[i], &matrix[j]);
+            let (done, current) = matrix.split_at_mut(i);
+            for (value, basis) in current[0].iter_mut().zip(&done[j]) {
+                *value -= projection * basis;
+            }
+        }
+        let norm : f64 = dot(&matrix[i], &matrix[i]).sqrt();
+        if norm > 0.0 {
+            for value in matrix[i].iter_mut() {
+                *value /= norm;
+            }
+        }
+    }
+    matrix
+}
+
+fn dot(first : &[f64], second : &[f64]) -> f64 {
+    first.iter().zip(second).map(|(a, b)| a * b).sum()
+}
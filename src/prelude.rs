@@ -1,7 +1,10 @@
 pub use crate::neural_network_parts::neural_network::NeuralNetwork;
+pub use crate::neural_network_parts::activator::{Activation, register_activation};
 pub use crate::data_and_config::neural_net_config_parts::NeuralNetSettings;
 pub use crate::data_and_config::dataset::DataSet;
+pub use crate::data_and_config::validate::validate;
 pub use crate::data_and_config::test_config::{TestConfig, Test};
 pub use crate::classifier_parts::nnet_trainer::NNetTrainer;
 pub use crate::classifier_parts::breeder::Breeder;
+pub use crate::tinman_error::TinmanError;
 //pub use crate::classifier_parts::classifier::Classifier;
\ No newline at end of file
@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// Everything that can go wrong because of the settings, data or files handed to the crate.
+// Layers and rows are counted from 0.
+#[derive(Debug)]
+pub enum TinmanError {
+    UnknownActivation { layer : usize, name : String },
+    ActivationNameTaken { name : String },
+    UnknownLoss { name : String },
+    UnknownTask { name : String },
+    UnknownPrecision { name : String },
+    UnknownOptimizer { layer : usize, name : String },
+    UnknownSchedule { layer : usize, name : String },
+    UnknownInit { layer : usize, name : String },
+    UnknownLayerType { layer : usize, name : String },
+    NoLayers,
+    EmptyLayer { layer : usize },
+    InvalidWeightRange { layer : usize, low : f64, high : f64 },
+    InvalidDropoutRate { layer : usize, rate : f64 },
+    LayerCountMismatch { expected : usize, actual : usize },
+    PrecisionMismatch { expected : String, actual : String },
+    UnitCountMismatch { layer : usize, expected : usize, actual : usize },
+    MissingWeights { layer : usize },
+    WeightVectorCountMismatch { layer : usize, expected : usize, actual : usize },
+    WeightLengthMismatch { layer : usize, node : usize, expected : usize, actual : usize },
+    BiasCountMismatch { layer : usize, expected : usize, actual : usize },
+    BatchNormSizeMismatch { layer : usize, expected : usize, actual : usize },
+    RunningStatsLengthMismatch { layer : usize, expected : usize, actual : usize },
+    ActivationParameterCountMismatch { layer : usize, expected : usize, actual : usize },
+    HiddenSoftmax { layer : usize },
+    InputSizeMismatch { row : Option<usize>, expected : usize, actual : usize },
+    TargetSizeMismatch { row : Option<usize>, expected : usize, actual : usize },
+    LabelOutOfRange { row : Option<usize>, label : usize, output_units : usize },
+    LabelBeyondResultMap { row : usize, label : usize, classes : usize },
+    RowCountMismatch { expected : usize, actual : usize },
+    NonFiniteFeature { row : usize, column : usize },
+    NonFiniteTarget { row : usize, target : usize },
+    NoTrainingRows,
+    NoTestRows,
+    BatchNormNeedsBatch { batch_size : usize },
+    FailedPrediction,
+    NotABinaryModelFile,
+    CorruptModelFile,
+    RaggedWeightVectors { layer : usize },
+    UnsupportedFormatVersion { version : u32, supported : u32 },
+    WrongModelKind { expected : String, found : String },
+    Io(io::Error)
+}
+
+impl TinmanError {
+    // Errors about one row of data are raised without knowing which row it was,
+    // callers that loop over a dataset fill it in.
+    pub fn at_row(self, index : usize) -> TinmanError {
+        match self {
+            TinmanError::InputSizeMismatch { expected, actual, .. } =>
+                TinmanError::InputSizeMismatch { row : Some(index), expected, actual },
+            TinmanError::TargetSizeMismatch { expected, actual, .. } =>
+                TinmanError::TargetSizeMismatch { row : Some(index), expected, actual },
+            TinmanError::LabelOutOfRange { label, output_units, .. } =>
+                TinmanError::LabelOutOfRange { row : Some(index), label, output_units },
+            error => error
+        }
+    }
+}
+
+impl fmt::Display for TinmanError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TinmanError::UnknownActivation { layer, name } =>
+                write!(f, "layer {}: activation function {:?} not found in list of functions", layer, name),
+            TinmanError::ActivationNameTaken { name } =>
+                write!(f, "activation function {:?} is built in and can't be registered", name),
+            TinmanError::UnknownLoss { name } =>
+                write!(f, "loss function {:?} not found in list of functions", name),
+            TinmanError::UnknownTask { name } =>
+                write!(f, "task {:?} not found, expected classification, regression or multi_label", name),
+            TinmanError::UnknownPrecision { name } =>
+                write!(f, "precision {:?} not found, expected f64 or f32", name),
+            TinmanError::UnknownOptimizer { layer, name } =>
+                write!(f, "layer {}: optimizer {:?} not found in list of optimizers", layer, name),
+            TinmanError::UnknownSchedule { layer, name } =>
+                write!(f, "layer {}: schedule {:?} not found in list of learning rate schedules", layer, name),
+            TinmanError::UnknownInit { layer, name } =>
+                write!(f, "layer {}: init strategy {:?} not found in list of init strategies", layer, name),
+            TinmanError::UnknownLayerType { layer, name } =>
+                write!(f, "layer {}: layer type {:?} not found, expected dense or batch_norm", layer, name),
+            TinmanError::NoLayers =>
+                write!(f, "neural network settings have no layers"),
+            TinmanError::EmptyLayer { layer } =>
+                write!(f, "layer {}: layer has no units", layer),
+            TinmanError::InvalidWeightRange { layer, low, high } =>
+                write!(f, "layer {}: weight range starts at {}, above its end {}", layer, low, high),
+            TinmanError::InvalidDropoutRate { layer, rate } =>
+                write!(f, "layer {}: dropout rate {} is outside [0, 1)", layer, rate),
+            TinmanError::LayerCountMismatch { expected, actual } =>
+                write!(f, "settings have {} layers, network has {}", actual, expected),
+            TinmanError::PrecisionMismatch { expected, actual } =>
+                write!(f, "settings use {} precision, network uses {}", actual, expected),
+            TinmanError::UnitCountMismatch { layer, expected, actual } =>
+                write!(f, "layer {}: settings have {} units, network has {}", layer, actual, expected),
+            TinmanError::MissingWeights { layer } =>
+                write!(f, "layer {}: no weight vectors found", layer),
+            TinmanError::WeightVectorCountMismatch { layer, expected, actual } =>
+                write!(f, "layer {}: {} weight vectors for {} units", layer, actual, expected),
+            TinmanError::WeightLengthMismatch { layer, node, expected, actual } =>
+                write!(f, "layer {}, node {}: weight vector has {} weights, expected {}", layer, node, actual, expected),
+            TinmanError::BiasCountMismatch { layer, expected, actual } =>
+                write!(f, "layer {}: {} biases for {} units", layer, actual, expected),
+            TinmanError::BatchNormSizeMismatch { layer, expected, actual } =>
+                write!(f, "layer {}: batch norm layer has {} units, the layer before it has {}", layer, actual, expected),
+            TinmanError::RunningStatsLengthMismatch { layer, expected, actual } =>
+                write!(f, "layer {}: {} running statistics for {} units", layer, actual, expected),
+            TinmanError::ActivationParameterCountMismatch { layer, expected, actual } =>
+                write!(f, "layer {}: {} activation parameters, expected {}", layer, actual, expected),
+            TinmanError::HiddenSoftmax { layer } =>
+                write!(f, "layer {}: softmax can only be used on the output layer", layer),
+            TinmanError::InputSizeMismatch { row, expected, actual } =>
+                write!(f, "{}{} inputs, network expects {}", row_prefix(row), actual, expected),
+            TinmanError::TargetSizeMismatch { row, expected, actual } =>
+                write!(f, "{}{} targets, network has {} outputs", row_prefix(row), actual, expected),
+            TinmanError::LabelOutOfRange { row, label, output_units } =>
+                write!(f, "{}label {} is out of range for {} output units", row_prefix(row), label, output_units),
+            TinmanError::LabelBeyondResultMap { row, label, classes } =>
+                write!(f, "row {}: label {} is beyond the {} classes in the result map", row, label, classes),
+            TinmanError::RowCountMismatch { expected, actual } =>
+                write!(f, "{} rows given, the last forward pass had {}", actual, expected),
+            TinmanError::NonFiniteFeature { row, column } =>
+                write!(f, "row {}: column {} is not a finite number", row, column),
+            TinmanError::NonFiniteTarget { row, target } =>
+                write!(f, "row {}: target {} is not a finite number", row, target),
+            TinmanError::NoTrainingRows =>
+                write!(f, "no rows before the train test boundary to train on"),
+            TinmanError::NoTestRows =>
+                write!(f, "no rows after the train test boundary to test on"),
+            TinmanError::BatchNormNeedsBatch { batch_size } =>
+                write!(f, "batch norm layers need a batch size of at least 2, batch size is {}", batch_size),
+            TinmanError::FailedPrediction =>
+                write!(f, "neural network failed to find a prediction, outputs are not finite"),
+            TinmanError::NotABinaryModelFile =>
+                write!(f, "file is not a Tinman binary model file"),
+            TinmanError::CorruptModelFile =>
+                write!(f, "binary model file is truncated or corrupt, a length in it does not fit the rest of the file"),
+            TinmanError::RaggedWeightVectors { layer } =>
+                write!(f, "layer {}: weight vectors must all have the same length to be saved in binary format", layer),
+            TinmanError::UnsupportedFormatVersion { version, supported } =>
+                write!(f, "model file format version {} is newer than supported version {}", version, supported),
+            TinmanError::WrongModelKind { expected, found } =>
+                write!(f, "expected a saved {}, found a saved {}", expected, found),
+            TinmanError::Io(error) =>
+                write!(f, "{}", error)
+        }
+    }
+}
+
+impl Error for TinmanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TinmanError::Io(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for TinmanError {
+    fn from(error : io::Error) -> TinmanError {
+        TinmanError::Io(error)
+    }
+}
+
+// model files that aren't valid json are reported the way serde_json reads them, as io errors
+impl From<serde_json::Error> for TinmanError {
+    fn from(error : serde_json::Error) -> TinmanError {
+        TinmanError::Io(error.into())
+    }
+}
+
+fn row_prefix(row : &Option<usize>) -> String {
+    match row {
+        Some(row) => format!("row {}: ", row),
+        None => String::new()
+    }
+}
@@ -0,0 +1,60 @@
+use Tinman::neural_network_parts::activator::{
+    Activation,
+    get_activation_names,
+    get_activation_by_name,
+    check_derivatives
+};
+use std::sync::Arc;
+
+fn activation(name : &str) -> Arc<dyn Activation> {
+    get_activation_by_name(name).unwrap()
+}
+
+#[test]
+fn every_derivative_matches_finite_differences() {
+    for name in get_activation_names() {
+        let error : f64 = check_derivatives(&*activation(&name));
+        assert!(error < 1e-6, "{}: derivative off by {}", name, error);
+    }
+}
+
+#[test]
+fn saturating_activations_stay_finite() {
+    assert_eq!(activation("tanh").activate(1000.0, &[]), 1.0);
+    assert_eq!(activation("tanh").activate(-1000.0, &[]), -1.0);
+    assert_eq!(activation("tanh").derivative(1000.0, &[]), 0.0);
+    assert_eq!(activation("softplus").activate(1000.0, &[]), 1000.0);
+    assert_eq!(activation("softplus").activate(-1000.0, &[]), 0.0);
+    assert_eq!(activation("gelu").derivative(1000.0, &[]), 1.0);
+}
+
+#[test]
+fn fixed_derivatives() {
+    assert_eq!(activation("default").derivative(3.0, &[]), 1.0);
+    assert_eq!(activation("binary_step").derivative(3.0, &[]), 0.0);
+    assert_eq!(activation("sqnl").derivative(-1.0, &[]), 0.5);
+    assert_eq!(activation("sqnl").activate(3.0, &[]), 1.0);
+}
+
+struct Cube;
+
+impl Activation for Cube {
+    fn get_name(&self) -> &str {
+        "cube"
+    }
+
+    fn activate(&self, x : f64, _parameters : &[f64]) -> f64 {
+        x * x * x
+    }
+
+    fn derivative(&self, x : f64, _parameters : &[f64]) -> f64 {
+        3.0 * x * x
+    }
+}
+
+#[test]
+fn registered_activations_are_checked() {
+    Tinman::register_activation(Arc::new(Cube)).unwrap();
+    assert!(get_activation_names().contains(&"cube".to_string()));
+    assert!(check_derivatives(&*activation("cube")) < 1e-6);
+}
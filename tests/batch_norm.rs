@@ -0,0 +1,186 @@
+mod common;
+
+use common::{layer, settings, dataset};
+use Tinman::{NeuralNetwork, NNetTrainer, Breeder, NeuralNetSettings, DataSet, TinmanError};
+use Tinman::data_and_config::neural_net_config_parts::LayerSettings;
+
+fn batch_norm(output_units : usize) -> LayerSettings {
+    let mut batch_norm : LayerSettings = layer("linear", output_units);
+    batch_norm.layer_type = Some("batch_norm".to_string());
+    batch_norm
+}
+
+// plain gradient descent with a learning rate of 1, so a weight moves by exactly its mean gradient
+fn sgd(mut layer_settings : LayerSettings) -> LayerSettings {
+    layer_settings.learning_rate = 1.0;
+    layer_settings.momentum = 0.0;
+    layer_settings
+}
+
+fn rows_and_targets(nnet : &NeuralNetwork, data : &DataSet) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let rows : Vec<Vec<f64>> = data.data.iter().map(|row| row.columns.clone()).collect();
+    let targets : Vec<Vec<f64>> = data.data.iter().map(|row| nnet.multi_hot_targets(&[row.label])).collect();
+    (rows, targets)
+}
+
+// summed loss of the rows run through as one training batch
+fn batch_loss(nnet_settings : &NeuralNetSettings, data : &DataSet) -> f64 {
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(nnet_settings).unwrap();
+    let (rows, targets) = rows_and_targets(&nnet, data);
+    nnet.set_training(true);
+    nnet.forward_rows(&rows).unwrap();
+    nnet.get_loss_for_rows(&targets)
+}
+
+// a tanh layer feeding batch norm, as a regression network so the outputs are the normalized values
+fn normalized_settings() -> NeuralNetSettings {
+    let mut nnet_settings : NeuralNetSettings = settings(vec![layer("tanh", 3), batch_norm(3)]);
+    nnet_settings.task = Some("regression".to_string());
+    nnet_settings.layers[1].bias = 0.0;
+    nnet_settings
+}
+
+fn columns(data : &DataSet) -> Vec<Vec<f64>> {
+    data.data.iter().map(|row| row.columns.clone()).collect()
+}
+
+#[test]
+fn normalizes_with_batch_statistics_while_training() {
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&normalized_settings()).unwrap();
+    let rows : Vec<Vec<f64>> = columns(&dataset(16));
+    nnet.set_training(true);
+    nnet.forward_rows(&rows).unwrap();
+    // mse is half the squared error, so against targets of 0 and 1 the losses give
+    // the sum and the sum of squares of the outputs
+    let values : f64 = (rows.len() * 3) as f64;
+    let squared_sum : f64 = 2.0 * nnet.get_loss_for_rows(&vec![vec![0.0; 3]; rows.len()]);
+    let shifted_squared_sum : f64 = 2.0 * nnet.get_loss_for_rows(&vec![vec![1.0; 3]; rows.len()]);
+    let sum : f64 = (squared_sum - shifted_squared_sum + values) / 2.0;
+    assert!(sum.abs() < 1e-9, "outputs sum to {}", sum);
+    assert!((squared_sum / values - 1.0).abs() < 1e-3, "mean squared output {}", squared_sum / values);
+}
+
+#[test]
+fn running_statistics_follow_the_batches() {
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&normalized_settings()).unwrap();
+    let rows : Vec<Vec<f64>> = columns(&dataset(16));
+    nnet.set_training(true);
+    nnet.forward_rows(&rows).unwrap();
+    let trained : NeuralNetSettings = nnet.get_settings();
+
+    // the inputs batch norm saw are the outputs of the tanh layer on its own
+    let mut below_settings : NeuralNetSettings = trained.clone();
+    below_settings.layers.truncate(1);
+    let mut below : NeuralNetwork = NeuralNetwork::new(&below_settings).unwrap();
+    let inputs : Vec<Vec<f64>> = rows.iter().map(|row| below.predict_values(row).unwrap()).collect();
+    let count : f64 = rows.len() as f64;
+    for unit in 0 .. 3 {
+        let mean : f64 = inputs.iter().map(|row| row[unit]).sum::<f64>() / count;
+        let variance : f64 = inputs.iter().map(|row| (row[unit] - mean).powi(2)).sum::<f64>() / (count - 1.0);
+        let running_mean : f64 = trained.layers[1].running_mean.as_ref().unwrap()[unit];
+        let running_variance : f64 = trained.layers[1].running_variance.as_ref().unwrap()[unit];
+        assert!((running_mean - 0.01 * mean).abs() < 1e-12);
+        assert!((running_variance - (0.99 + 0.01 * variance)).abs() < 1e-12);
+    }
+
+    // inference normalizes with the running statistics, not the batch's
+    let mut fresh : NeuralNetwork = NeuralNetwork::new(&normalized_settings()).unwrap();
+    assert!(nnet.predict_values(&rows[0]).unwrap() != fresh.predict_values(&rows[0]).unwrap());
+}
+
+#[test]
+fn backpropagates_through_batch_statistics() {
+    let data : DataSet = dataset(8);
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&settings(vec![
+        sgd(layer("tanh", 3)),
+        sgd(batch_norm(3)),
+        sgd(layer("softmax", 3))
+    ])).unwrap();
+    let start : NeuralNetSettings = nnet.get_settings();
+    let (rows, targets) = rows_and_targets(&nnet, &data);
+    nnet.set_training(true);
+    nnet.forward_rows(&rows).unwrap();
+    nnet.set_error_deltas(&targets).unwrap();
+    nnet.accumulate_gradients_for_rows(&rows).unwrap();
+    nnet.apply_gradients();
+    let stepped : NeuralNetSettings = nnet.get_settings();
+
+    // the weights of the layer below feel the batch statistics too, so check those as well as gamma and beta
+    let epsilon : f64 = 1e-6;
+    for layer in 0 .. 2 {
+        let units : usize = start.layers[layer].output_units;
+        for unit in 0 .. units {
+            let weight_count : usize = start.layers[layer].layer_weights.as_ref().unwrap()[unit].len();
+            for index in 0 .. weight_count + 1 {
+                let value = |nnet_settings : &NeuralNetSettings| -> f64 {
+                    let layer_settings : &LayerSettings = &nnet_settings.layers[layer];
+                    if index < weight_count {
+                        layer_settings.layer_weights.as_ref().unwrap()[unit][index]
+                    } else {
+                        layer_settings.layer_biases.as_ref().unwrap()[unit]
+                    }
+                };
+                let moved = |change : f64| -> NeuralNetSettings {
+                    let mut nnet_settings : NeuralNetSettings = start.clone();
+                    let layer_settings : &mut LayerSettings = &mut nnet_settings.layers[layer];
+                    if index < weight_count {
+                        layer_settings.layer_weights.as_mut().unwrap()[unit][index] += change;
+                    } else {
+                        layer_settings.layer_biases.as_mut().unwrap()[unit] += change;
+                    }
+                    nnet_settings
+                };
+                let analytical : f64 = (value(&start) - value(&stepped)) * rows.len() as f64;
+                let numerical : f64 = (batch_loss(&moved(epsilon), &data) - batch_loss(&moved(-epsilon), &data)) / (2.0 * epsilon);
+                assert!(
+                    (analytical - numerical).abs() < 1e-6 * analytical.abs().max(1.0),
+                    "layer {}, unit {}, value {}: backprop {} finite differences {}", layer, unit, index, analytical, numerical
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn training_needs_batches_of_two_rows() {
+    let nnet_settings : NeuralNetSettings = settings(vec![layer("tanh", 3), batch_norm(3), layer("softmax", 3)]);
+    let mut trainer : NNetTrainer = NNetTrainer::new(&nnet_settings, 40, 2).unwrap();
+    assert!(matches!(trainer.train(&dataset(50)), Err(TinmanError::BatchNormNeedsBatch { batch_size : 1 })));
+    trainer.set_batch_size(4);
+    trainer.train(&dataset(50)).unwrap();
+    trainer.test(&dataset(50)).unwrap();
+    assert!(!trainer.has_diverged());
+}
+
+#[test]
+fn gamma_gets_no_penalty_or_max_norm() {
+    let mut normalize : LayerSettings = batch_norm(3);
+    normalize.l1 = Some(5.0);
+    normalize.l2 = Some(50.0);
+    normalize.max_norm = Some(0.01);
+    let data : DataSet = dataset(8);
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&settings(vec![layer("tanh", 3), normalize, layer("softmax", 3)])).unwrap();
+    let (rows, targets) = rows_and_targets(&nnet, &data);
+    nnet.set_training(true);
+    nnet.forward_rows(&rows).unwrap();
+    nnet.set_error_deltas(&targets).unwrap();
+    nnet.accumulate_gradients_for_rows(&rows).unwrap();
+    nnet.apply_gradients();
+    // either penalty or max norm would pull gamma far from its start of 1
+    for gamma in nnet.get_settings().layers[1].layer_weights.as_ref().unwrap().concat() {
+        assert!((gamma - 1.0).abs() < 0.2, "gamma moved to {}", gamma);
+    }
+}
+
+#[test]
+fn breeder_leaves_gamma_to_training() {
+    let mut parent : NeuralNetSettings = NeuralNetwork::new(&settings(vec![layer("tanh", 3), batch_norm(3), layer("softmax", 3)]))
+        .unwrap()
+        .get_settings();
+    let mut child : NeuralNetSettings = parent.clone();
+    let mut breeder : Breeder = Breeder::new(0.1, 0.1);
+    breeder.reseed(5);
+    breeder.child_w_sa_weights(&mut child, &mut parent).unwrap();
+    assert!(child.layers[0].layer_weights != parent.layers[0].layer_weights);
+    assert!(child.layers[1].layer_weights == parent.layers[1].layer_weights);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{layer, settings};
+use Tinman::{Breeder, NeuralNetSettings};
+use Tinman::data_and_config::neural_net_config_parts::{LayerSettings, ScheduleSettings};
+
+#[test]
+fn regularization_mutates_on_a_log_scale_and_stays_off_when_unset() {
+    let mut regularized : LayerSettings = layer("tanh", 4);
+    regularized.l1 = Some(1e-4);
+    regularized.l2 = Some(1e-3);
+    regularized.max_norm = Some(3.0);
+    let mut parent : NeuralNetSettings = settings(vec![regularized, layer("softmax", 3)]);
+    let mut breeder : Breeder = Breeder::new(0.1, 0.1);
+    breeder.reseed(5);
+    for _ in 0 .. 50 {
+        let mut child : NeuralNetSettings = parent.clone();
+        breeder.child_w_sa_hyper_parameters(&mut child, &mut parent);
+        // at full temperature a step multiplies or divides by at most e
+        let pairs : Vec<(Option<f64>, Option<f64>)> = vec![
+            (child.layers[0].l1, parent.layers[0].l1),
+            (child.layers[0].l2, parent.layers[0].l2),
+            (child.layers[0].max_norm, parent.layers[0].max_norm)
+        ];
+        for (child_value, parent_value) in pairs {
+            let ratio : f64 = child_value.unwrap() / parent_value.unwrap();
+            assert!(ratio >= (-1.0f64).exp() && ratio <= 1.0f64.exp(), "changed by a factor of {}", ratio);
+        }
+        assert!(child.layers[1].l1.is_none() && child.layers[1].l2.is_none() && child.layers[1].max_norm.is_none());
+    }
+}
+
+#[test]
+fn regularization_both_grows_and_shrinks() {
+    let mut regularized : LayerSettings = layer("tanh", 4);
+    regularized.l1 = Some(1e-4);
+    regularized.l2 = Some(1e-3);
+    regularized.max_norm = Some(3.0);
+    let mut parent : NeuralNetSettings = settings(vec![regularized, layer("softmax", 3)]);
+    let mut breeder : Breeder = Breeder::new(0.1, 0.1);
+    breeder.reseed(9);
+    // per value, how many children had it larger and how many smaller than the parent
+    let mut grown : [usize; 3] = [0; 3];
+    let mut shrunk : [usize; 3] = [0; 3];
+    for _ in 0 .. 200 {
+        let mut child : NeuralNetSettings = parent.clone();
+        breeder.child_w_sa_hyper_parameters(&mut child, &mut parent);
+        let pairs : Vec<(Option<f64>, Option<f64>)> = vec![
+            (child.layers[0].l1, parent.layers[0].l1),
+            (child.layers[0].l2, parent.layers[0].l2),
+            (child.layers[0].max_norm, parent.layers[0].max_norm)
+        ];
+        for (i, (child_value, parent_value)) in pairs.into_iter().enumerate() {
+            if child_value.unwrap() > parent_value.unwrap() {
+                grown[i] += 1;
+            } else if child_value.unwrap() < parent_value.unwrap() {
+                shrunk[i] += 1;
+            }
+        }
+    }
+    for i in 0 .. 3 {
+        assert!(grown[i] > 20 && shrunk[i] > 20, "value {} grew {} times and shrank {} times", i, grown[i], shrunk[i]);
+    }
+}
+
+fn scheduled(step_rounds : usize, patience : usize, cycle_multiplier : f64) -> NeuralNetSettings {
+    let mut scheduled_layer : LayerSettings = layer("tanh", 4);
+    scheduled_layer.learning_rate_schedule = Some(ScheduleSettings {
+        schedule : "cosine_restarts".to_string(),
+        decay_rate : Some(0.5),
+        step_rounds : Some(step_rounds),
+        min_learning_rate : Some(0.001),
+        patience : Some(patience),
+        cycle_multiplier : Some(cycle_multiplier)
+    });
+    settings(vec![scheduled_layer, layer("softmax", 3)])
+}
+
+#[test]
+fn schedule_round_counts_and_multiplier_are_blended() {
+    let parent1 : NeuralNetSettings = scheduled(10, 2, 2.0);
+    let parent2 : NeuralNetSettings = scheduled(30, 20, 4.0);
+    let mut child : NeuralNetSettings = parent1.clone();
+    let mut breeder : Breeder = Breeder::new(0.1, 0.1);
+    breeder.reseed(5);
+    // parent 1 starts out favoured 0.95 to 0.05
+    breeder.child_w_ga_hyper_params(&mut child, &parent1, &parent2);
+    let schedule : ScheduleSettings = child.layers[0].learning_rate_schedule.clone().unwrap();
+    assert_eq!(schedule.step_rounds, Some(11));
+    assert_eq!(schedule.patience, Some(3));
+    assert!((schedule.cycle_multiplier.unwrap() - 2.1).abs() < 1e-12);
+}
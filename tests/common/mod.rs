@@ -0,0 +1,86 @@
+// Settings and data shared by the integration tests, each test file uses part of it.
+#![allow(dead_code)]
+
+use Tinman::DataSet;
+use Tinman::data_and_config::dataset::{Row, TableInfo};
+use Tinman::data_and_config::neural_net_config_parts::{NeuralNetSettings, LayerSettings};
+
+pub fn layer(activation_function : &str, output_units : usize) -> LayerSettings {
+    LayerSettings {
+        activation_function : activation_function.to_string(),
+        activation_parameters : None,
+        weight_range : [-0.5, 0.5],
+        init : None,
+        layer_weights : None,
+        layer_biases : None,
+        output_units,
+        bias : 0.1,
+        learning_rate : 0.1,
+        momentum : 0.0,
+        optimizer : None,
+        beta1 : None,
+        beta2 : None,
+        epsilon : None,
+        learning_rate_schedule : None,
+        l1 : None,
+        l2 : None,
+        max_norm : None,
+        dropout_rate : None,
+        layer_type : None,
+        running_mean : None,
+        running_variance : None
+    }
+}
+
+// a seeded classifier over the two columns of dataset
+pub fn settings(layers : Vec<LayerSettings>) -> NeuralNetSettings {
+    NeuralNetSettings {
+        query_id : 0,
+        config_id : 1,
+        accuracy : 0.0,
+        input_size : 2,
+        loss : None,
+        task : None,
+        label_threshold : None,
+        seed : Some(11),
+        precision : None,
+        layers
+    }
+}
+
+// Two columns in [-1, 1) from a fixed generator. Each row has a label out of three
+// classes, two regression targets and a set of labels, so every task can train on it.
+pub fn dataset(row_count : usize) -> DataSet {
+    let mut state : u64 = 7;
+    let mut next = move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((state >> 33) as f64 / 2f64.powi(31)) * 2.0 - 1.0
+    };
+    let mut data : Vec<Row> = Vec::new();
+    for _ in 0 .. row_count {
+        let x : f64 = next();
+        let y : f64 = next();
+        let label : usize = if x > 0.3 { 0 } else if y > 0.0 { 1 } else { 2 };
+        let mut labels : Vec<usize> = Vec::new();
+        if x > 0.0 {
+            labels.push(0);
+        }
+        if y > 0.2 {
+            labels.push(1);
+        }
+        if x + y < 0.0 {
+            labels.push(2);
+        }
+        data.push(Row { label, columns : vec![x, y], labels, targets : vec![x * y + 0.5 * x, (3.0 * x).sin()] });
+    }
+    DataSet {
+        table_info : TableInfo { table_name : "test".to_string(), query_id : 0, column_names : None },
+        result_map : vec!["a".to_string(), "b".to_string(), "c".to_string()],
+        data
+    }
+}
+
+// a file in the temp directory that no other test uses
+pub fn temp_path(name : &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("tinman_test_{}_{}", std::process::id(), name))
+}
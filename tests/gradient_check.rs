@@ -0,0 +1,40 @@
+mod common;
+
+use common::{layer, settings};
+use Tinman::NeuralNetwork;
+use Tinman::data_and_config::neural_net_config_parts::LayerSettings;
+
+fn network(layers : Vec<LayerSettings>) -> NeuralNetwork {
+    NeuralNetwork::new(&settings(layers)).unwrap()
+}
+
+fn weights(nnet : &mut NeuralNetwork) -> Vec<Option<Vec<Vec<f64>>>> {
+    nnet.get_settings().layers.into_iter().map(|layer| layer.layer_weights).collect()
+}
+
+// errors reaching the lower layers must go through the weights the forward pass used
+#[test]
+fn backprop_matches_finite_differences() {
+    let mut nnet : NeuralNetwork = network(vec![layer("tanh", 5), layer("prelu", 4), layer("sigmoid", 4), layer("softmax", 3)]);
+    let inputs : Vec<f64> = vec![0.3, -0.8];
+    for error in nnet.gradient_check(&inputs, 2, 1e-5).unwrap() {
+        assert!(error.max_error < 1e-6, "gradient off by {}", error.max_error);
+        // dividing by gradients below 1 only makes the plain relative error larger,
+        // and none here are small enough for rounding to matter
+        assert!(error.max_relative_error >= error.max_error);
+        assert!(error.max_relative_error < 1e-4, "gradient off by {} relative", error.max_relative_error);
+    }
+}
+
+#[test]
+fn accumulating_leaves_weights_alone() {
+    let mut nnet : NeuralNetwork = network(vec![layer("tanh", 5), layer("softmax", 3)]);
+    let inputs : Vec<f64> = vec![0.3, -0.8];
+    let before : Vec<Option<Vec<Vec<f64>>>> = weights(&mut nnet);
+    nnet.forward(&inputs).unwrap();
+    nnet.set_error_delta(1).unwrap();
+    nnet.accumulate_gradients(&inputs).unwrap();
+    assert_eq!(weights(&mut nnet), before);
+    nnet.apply_gradients();
+    assert_ne!(weights(&mut nnet), before);
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::{layer, settings, dataset, temp_path};
+use Tinman::{NeuralNetwork, NNetTrainer, DataSet, TinmanError};
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+// a few rounds of training so the weights are nothing like the initial ones
+fn trained_network(data : &DataSet) -> NeuralNetwork {
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&settings(vec![layer("tanh", 6), layer("prelu", 4), layer("softmax", 3)])).unwrap();
+    for _ in 0 .. 3 {
+        for row in &data.data {
+            nnet.forward(&row.columns).unwrap();
+            nnet.set_error_delta(row.label).unwrap();
+            nnet.backward(&row.columns).unwrap();
+        }
+    }
+    nnet
+}
+
+fn outputs(nnet : &mut NeuralNetwork, data : &DataSet) -> Vec<Vec<f64>> {
+    data.data.iter().map(|row| nnet.predict_values(&row.columns).unwrap()).collect()
+}
+
+#[test]
+fn json_round_trip_keeps_predictions() {
+    let data : DataSet = dataset(60);
+    let mut nnet : NeuralNetwork = trained_network(&data);
+    let path : PathBuf = temp_path("round_trip.json");
+    nnet.save(&path).unwrap();
+    let mut loaded : NeuralNetwork = NeuralNetwork::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(outputs(&mut loaded, &data), outputs(&mut nnet, &data));
+}
+
+#[test]
+fn binary_round_trip_keeps_predictions() {
+    let data : DataSet = dataset(60);
+    let mut nnet : NeuralNetwork = trained_network(&data);
+    let path : PathBuf = temp_path("round_trip.bin");
+    nnet.save_binary(&path).unwrap();
+    let mut loaded : NeuralNetwork = NeuralNetwork::load_binary(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(outputs(&mut loaded, &data), outputs(&mut nnet, &data));
+}
+
+#[test]
+fn trainer_round_trip_keeps_test_result() {
+    let data : DataSet = dataset(80);
+    let mut trainer : NNetTrainer = NNetTrainer::new(&settings(vec![layer("tanh", 6), layer("softmax", 3)]), 60, 3).unwrap();
+    trainer.set_batch_size(4);
+    trainer.train(&data).unwrap();
+    trainer.test(&data).unwrap();
+    for (name, binary) in &[("trainer.json", false), ("trainer.bin", true)] {
+        let path : PathBuf = temp_path(name);
+        let mut loaded : NNetTrainer = if *binary {
+            trainer.save_binary(&path).unwrap();
+            NNetTrainer::load_binary(&path).unwrap()
+        } else {
+            trainer.save(&path).unwrap();
+            NNetTrainer::load(&path).unwrap()
+        };
+        fs::remove_file(&path).unwrap();
+        loaded.test(&data).unwrap();
+        assert_eq!(loaded.get_batch_size(), 4);
+        assert_eq!(loaded.get_test_result(), trainer.get_test_result());
+        assert_eq!(loaded.get_test_loss(), trainer.get_test_loss());
+    }
+}
+
+fn saved_binary(name : &str) -> (PathBuf, Vec<u8>) {
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&settings(vec![layer("tanh", 4), layer("softmax", 3)])).unwrap();
+    let path : PathBuf = temp_path(name);
+    nnet.save_binary(&path).unwrap();
+    let bytes : Vec<u8> = fs::read(&path).unwrap();
+    (path, bytes)
+}
+
+fn load_error(path : &PathBuf) -> TinmanError {
+    let result = NeuralNetwork::load_binary(path);
+    fs::remove_file(path).unwrap();
+    match result {
+        Err(error) => error,
+        Ok(_) => panic!("a malformed file loaded")
+    }
+}
+
+// the row and column counts of the first weight block, which follows a flag byte after the header
+fn weight_sizes_at(bytes : &[u8]) -> usize {
+    let header_len : usize = u64::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15]]) as usize;
+    16 + header_len + 1
+}
+
+#[test]
+fn truncated_binary_file_is_an_error() {
+    let (path, bytes) = saved_binary("truncated.bin");
+    fs::write(&path, &bytes[.. bytes.len() - 12]).unwrap();
+    match load_error(&path) {
+        TinmanError::CorruptModelFile => {},
+        TinmanError::Io(error) => assert_eq!(error.kind(), ErrorKind::UnexpectedEof),
+        error => panic!("expected a corrupt file error, got {}", error)
+    }
+}
+
+#[test]
+fn not_a_binary_model_file_is_an_error() {
+    let (path, mut bytes) = saved_binary("magic.bin");
+    bytes[0] = b'X';
+    fs::write(&path, &bytes).unwrap();
+    assert!(matches!(load_error(&path), TinmanError::NotABinaryModelFile));
+}
+
+#[test]
+fn oversized_header_length_is_an_error() {
+    let (path, mut bytes) = saved_binary("header_length.bin");
+    // the header length follows the magic and the format version
+    bytes[8 .. 16].copy_from_slice(&(1u64 << 62).to_le_bytes());
+    fs::write(&path, &bytes).unwrap();
+    assert!(matches!(load_error(&path), TinmanError::CorruptModelFile));
+}
+
+#[test]
+fn oversized_weight_rows_are_an_error() {
+    let (path, mut bytes) = saved_binary("weight_rows.bin");
+    let rows_at : usize = weight_sizes_at(&bytes);
+    bytes[rows_at .. rows_at + 8].copy_from_slice(&(1u64 << 61).to_le_bytes());
+    fs::write(&path, &bytes).unwrap();
+    assert!(matches!(load_error(&path), TinmanError::CorruptModelFile));
+}
+
+#[test]
+fn weight_rows_without_columns_are_an_error() {
+    let (path, mut bytes) = saved_binary("weight_columns.bin");
+    let rows_at : usize = weight_sizes_at(&bytes);
+    bytes[rows_at .. rows_at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    bytes[rows_at + 8 .. rows_at + 16].copy_from_slice(&0u64.to_le_bytes());
+    fs::write(&path, &bytes).unwrap();
+    assert!(matches!(load_error(&path), TinmanError::CorruptModelFile));
+}
+
+#[test]
+fn wrong_kind_of_model_file_is_an_error() {
+    let data : DataSet = dataset(20);
+    let mut trainer : NNetTrainer = NNetTrainer::new(&settings(vec![layer("tanh", 4), layer("softmax", 3)]), 15, 1).unwrap();
+    trainer.train(&data).unwrap();
+    let path : PathBuf = temp_path("wrong_kind.json");
+    trainer.save(&path).unwrap();
+    let result = NeuralNetwork::load(&path);
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(TinmanError::WrongModelKind { .. })));
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::{layer, settings, dataset};
+use Tinman::{DataSet, NeuralNetSettings, NeuralNetwork};
+use Tinman::data_and_config::neural_net_config_parts::LayerSettings;
+use Tinman::data_and_config::optimizer_parameters::OptimizerParameters;
+use Tinman::classifier_parts::optimize_nnet::{optimize_nnet_settings, optimize_nnet_settings_on_cores};
+
+// A short search over a small classifier, low enough on accuracy that it hands back settings.
+// Four temperature drops breed more candidates a round than there are workers, so workers train several each.
+fn order_form(seed : Option<u64>, cpus_to_use : usize) -> OptimizerParameters {
+    let mut nnet_settings : NeuralNetSettings = settings(vec![layer("tanh", 6), layer("softmax", 3)]);
+    nnet_settings.seed = None;
+    OptimizerParameters {
+        temperature_drops : 4,
+        heritability_bias_drops : 2,
+        current_candidate_configuration : Box::new(nnet_settings),
+        test_train_cutoff_idx : 150,
+        train_rounds_per_epoch : 2,
+        batch_size : None,
+        clip_value : None,
+        clip_norm : None,
+        max_train_epochs : 1,
+        max_config_changing_epochs : 1,
+        final_number_of_nnet_settings : 2,
+        winners_per_round : 2,
+        tuned_settings : Vec::new(),
+        min_acceptable_accuracy : 50.0,
+        cpus_to_use,
+        seed
+    }
+}
+
+// the tuned settings as json, so whole runs can be compared
+fn as_json(order_form : &OptimizerParameters) -> String {
+    assert!(order_form.tuned_settings.len() > 0);
+    serde_json::to_string(&order_form.tuned_settings).unwrap()
+}
+
+// eight cores are given, so cpus_to_use is the worker count whatever machine the tests run on
+fn tuned(seed : Option<u64>, cpus_to_use : usize) -> String {
+    let data : DataSet = dataset(200);
+    let mut order_form : OptimizerParameters = order_form(seed, cpus_to_use);
+    optimize_nnet_settings_on_cores(&mut order_form, &data, 8).unwrap();
+    as_json(&order_form)
+}
+
+#[test]
+fn same_seed_gives_the_same_tuned_settings() {
+    assert_eq!(tuned(Some(7), 2), tuned(Some(7), 2));
+    assert!(tuned(Some(7), 2) != tuned(Some(8), 2));
+}
+
+#[test]
+fn worker_count_does_not_change_the_tuned_settings() {
+    let two_workers : String = tuned(Some(7), 2);
+    assert_eq!(tuned(Some(7), 3), two_workers);
+    assert_eq!(tuned(Some(7), 8), two_workers);
+}
+
+#[test]
+fn machine_core_count_does_not_change_the_tuned_settings() {
+    let data : DataSet = dataset(200);
+    let mut order_form : OptimizerParameters = order_form(Some(7), 8);
+    optimize_nnet_settings(&mut order_form, &data).unwrap();
+    assert_eq!(as_json(&order_form), tuned(Some(7), 2));
+}
+
+// beta1 and beta2 are set off their defaults so a mix up between them shows
+fn with_optimizer(name : &str, learning_rate : f64, momentum : f64, activation_function : &str, output_units : usize) -> LayerSettings {
+    let mut layer_settings : LayerSettings = layer(activation_function, output_units);
+    layer_settings.optimizer = Some(name.to_string());
+    layer_settings.learning_rate = learning_rate;
+    layer_settings.momentum = momentum;
+    layer_settings.beta1 = Some(0.8);
+    layer_settings.beta2 = Some(0.9);
+    layer_settings
+}
+
+fn learned_values(nnet : &mut NeuralNetwork) -> Vec<f64> {
+    let mut values : Vec<f64> = Vec::new();
+    for layer_settings in nnet.get_settings().layers {
+        values.extend(layer_settings.layer_weights.unwrap().concat());
+        values.extend(layer_settings.layer_biases.unwrap());
+    }
+    values
+}
+
+// How far the first update on a single row moves each weight and bias, with the optimizer state
+// still at zero. Every network starts from the same seeded weights, so the gradients are the same
+// whichever optimizer turns them into steps.
+fn one_step(name : &str, learning_rate : f64, momentum : f64) -> Vec<f64> {
+    let layers : Vec<LayerSettings> = vec![
+        with_optimizer(name, learning_rate, momentum, "tanh", 4),
+        with_optimizer(name, learning_rate, momentum, "softmax", 3)
+    ];
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&settings(layers)).unwrap();
+    let before : Vec<f64> = learned_values(&mut nnet);
+    let inputs : Vec<f64> = vec![0.4, -0.7];
+    nnet.forward(&inputs).unwrap();
+    nnet.set_error_delta(1).unwrap();
+    nnet.accumulate_gradients(&inputs).unwrap();
+    nnet.apply_gradients();
+    before.iter().zip(learned_values(&mut nnet)).map(|(before, after)| before - after).collect()
+}
+
+// plain gradient descent with a learning rate of 1 steps by exactly the gradient
+fn gradients() -> Vec<f64> {
+    one_step("momentum", 1.0, 0.0)
+}
+
+fn assert_steps(name : &str, momentum : f64, step_of : impl Fn(f64) -> f64) {
+    let steps : Vec<f64> = one_step(name, 0.1, momentum);
+    let gradients : Vec<f64> = gradients();
+    assert!(gradients.iter().any(|gradient| gradient.abs() > 1e-3), "nothing to step");
+    for (step, gradient) in steps.iter().zip(&gradients) {
+        let expected : f64 = step_of(*gradient);
+        assert!((step - expected).abs() <= 1e-12 + 1e-9 * expected.abs(), "{}: stepped {} instead of {}", name, step, expected);
+    }
+}
+
+// the default, the layers don't set their own
+const EPSILON : f64 = 1e-8;
+
+#[test]
+fn nesterov_step_looks_ahead_along_the_velocity() {
+    // the velocity is lr * g, and the step adds momentum times it again
+    assert_steps("nesterov", 0.9, |gradient| 0.9 * 0.1 * gradient + 0.1 * gradient);
+}
+
+#[test]
+fn adagrad_step_divides_by_the_summed_squares() {
+    assert_steps("adagrad", 0.0, |gradient| 0.1 * gradient / ((gradient * gradient).sqrt() + EPSILON));
+}
+
+#[test]
+fn rmsprop_step_divides_by_the_decayed_squares() {
+    assert_steps("rmsprop", 0.0, |gradient| 0.1 * gradient / (((1.0 - 0.9) * gradient * gradient).sqrt() + EPSILON));
+}
+
+#[test]
+fn adam_step_uses_bias_corrected_moments() {
+    assert_steps("adam", 0.0, |gradient| {
+        let first_moment : f64 = (1.0 - 0.8) * gradient / (1.0 - 0.8);
+        let second_moment : f64 = (1.0 - 0.9) * gradient * gradient / (1.0 - 0.9);
+        0.1 * first_moment / (second_moment.sqrt() + EPSILON)
+    });
+}
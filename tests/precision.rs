@@ -0,0 +1,102 @@
+mod common;
+
+use common::{layer, settings, dataset, temp_path};
+use Tinman::{NeuralNetwork, NNetTrainer, NeuralNetSettings, DataSet};
+use std::fs;
+use std::path::PathBuf;
+
+fn single_precision(mut nnet_settings : NeuralNetSettings) -> NeuralNetSettings {
+    nnet_settings.precision = Some("f32".to_string());
+    nnet_settings
+}
+
+fn classifier() -> NeuralNetSettings {
+    settings(vec![layer("tanh", 6), layer("prelu", 4), layer("softmax", 3)])
+}
+
+fn trained(nnet_settings : &NeuralNetSettings, data : &DataSet) -> NNetTrainer {
+    let mut trainer : NNetTrainer = NNetTrainer::new(nnet_settings, 150, 10).unwrap();
+    trainer.set_batch_size(4);
+    trainer.train(data).unwrap();
+    trainer.test(data).unwrap();
+    trainer
+}
+
+// a few rounds of training a row at a time
+fn trained_network(nnet_settings : &NeuralNetSettings, data : &DataSet) -> NeuralNetwork {
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(nnet_settings).unwrap();
+    for _ in 0 .. 3 {
+        for row in &data.data {
+            nnet.forward(&row.columns).unwrap();
+            nnet.set_error_delta(row.label).unwrap();
+            nnet.backward(&row.columns).unwrap();
+        }
+    }
+    nnet
+}
+
+fn outputs(nnet : &mut NeuralNetwork, data : &DataSet) -> Vec<Vec<f64>> {
+    data.data.iter().map(|row| nnet.predict_values(&row.columns).unwrap()).collect()
+}
+
+#[test]
+fn f32_trains_about_as_well_as_f64() {
+    let data : DataSet = dataset(200);
+    let single : NNetTrainer = trained(&single_precision(classifier()), &data);
+    let double : NNetTrainer = trained(&classifier(), &data);
+    assert!(!single.has_diverged());
+    assert!(single.get_test_result() > 80.0, "{}% correct", single.get_test_result());
+    assert!((single.get_test_result() - double.get_test_result()).abs() <= 4.0);
+}
+
+#[test]
+fn f32_network_holds_its_values_as_f32() {
+    let data : DataSet = dataset(200);
+    let mut nnet : NeuralNetwork = trained_network(&single_precision(classifier()), &data);
+    let trained_settings : NeuralNetSettings = nnet.get_settings();
+    assert_eq!(trained_settings.precision, Some("f32".to_string()));
+    for layer_settings in &trained_settings.layers {
+        let mut values : Vec<f64> = layer_settings.layer_weights.as_ref().unwrap().concat();
+        values.extend(layer_settings.layer_biases.as_ref().unwrap());
+        assert!(values.iter().all(|value| *value as f32 as f64 == *value));
+    }
+}
+
+#[test]
+fn f32_network_reloads_with_the_same_predictions() {
+    let data : DataSet = dataset(200);
+    let mut nnet : NeuralNetwork = trained_network(&single_precision(classifier()), &data);
+    for (name, binary) in &[("f32.json", false), ("f32.bin", true)] {
+        let path : PathBuf = temp_path(name);
+        let mut loaded : NeuralNetwork = if *binary {
+            nnet.save_binary(&path).unwrap();
+            NeuralNetwork::load_binary(&path).unwrap()
+        } else {
+            nnet.save(&path).unwrap();
+            NeuralNetwork::load(&path).unwrap()
+        };
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.get_settings().precision, Some("f32".to_string()));
+        assert_eq!(outputs(&mut loaded, &data), outputs(&mut nnet, &data));
+    }
+}
+
+#[test]
+fn f32_trainer_reloads_with_the_same_test_result() {
+    let data : DataSet = dataset(200);
+    let mut trainer : NNetTrainer = trained(&single_precision(classifier()), &data);
+    for (name, binary) in &[("f32_trainer.json", false), ("f32_trainer.bin", true)] {
+        let path : PathBuf = temp_path(name);
+        let mut loaded : NNetTrainer = if *binary {
+            trainer.save_binary(&path).unwrap();
+            NNetTrainer::load_binary(&path).unwrap()
+        } else {
+            trainer.save(&path).unwrap();
+            NNetTrainer::load(&path).unwrap()
+        };
+        fs::remove_file(&path).unwrap();
+        loaded.test(&data).unwrap();
+        assert_eq!(loaded.get_test_result(), trainer.get_test_result());
+        assert_eq!(loaded.get_test_loss(), trainer.get_test_loss());
+    }
+}
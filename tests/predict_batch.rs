@@ -0,0 +1,46 @@
+mod common;
+
+use common::{layer, settings, dataset};
+use Tinman::{NeuralNetwork, NeuralNetSettings, DataSet, TinmanError};
+use Tinman::data_and_config::neural_net_config_parts::LayerSettings;
+
+// dropout and batch norm both act differently in training, a batch must still predict like single rows
+fn network() -> NeuralNetwork {
+    let mut dropped : LayerSettings = layer("tanh", 6);
+    dropped.dropout_rate = Some(0.3);
+    let mut batch_norm : LayerSettings = layer("linear", 6);
+    batch_norm.layer_type = Some("batch_norm".to_string());
+    batch_norm.running_mean = Some(vec![0.1; 6]);
+    batch_norm.running_variance = Some(vec![0.5; 6]);
+    let nnet_settings : NeuralNetSettings = settings(vec![dropped, batch_norm, layer("prelu", 5), layer("softmax", 3)]);
+    NeuralNetwork::new(&nnet_settings).unwrap()
+}
+
+fn columns(data : &DataSet) -> Vec<Vec<f64>> {
+    data.data.iter().map(|row| row.columns.clone()).collect()
+}
+
+#[test]
+fn predict_batch_matches_predict_row_by_row() {
+    let mut nnet : NeuralNetwork = network();
+    // more rows than a single chunk of the batched pass
+    let rows : Vec<Vec<f64>> = columns(&dataset(600));
+    let predictions : Vec<usize> = nnet.predict_batch(&rows).unwrap();
+    let outputs : Vec<Vec<f64>> = nnet.forward_batch(&rows).unwrap();
+    assert_eq!(predictions.len(), rows.len());
+    for (i, row) in rows.iter().enumerate() {
+        assert_eq!(predictions[i], nnet.predict(row).unwrap(), "row {}", i);
+        let row_outputs : Vec<f64> = nnet.predict_values(row).unwrap();
+        assert_eq!(outputs[i], row_outputs, "row {}", i);
+    }
+}
+
+#[test]
+fn predict_batch_names_the_bad_row() {
+    let mut nnet : NeuralNetwork = network();
+    let mut rows : Vec<Vec<f64>> = columns(&dataset(10));
+    rows[7].push(0.5);
+    let result : Result<Vec<usize>, TinmanError> = nnet.predict_batch(&rows);
+    assert!(matches!(result, Err(TinmanError::InputSizeMismatch { row : Some(7), expected : 2, actual : 3 })));
+    assert_eq!(nnet.predict_batch(&Vec::<Vec<f64>>::new()).unwrap(), Vec::<usize>::new());
+}
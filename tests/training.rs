@@ -0,0 +1,132 @@
+mod common;
+
+use common::{layer, settings, dataset};
+use Tinman::{NeuralNetwork, NNetTrainer, NeuralNetSettings, DataSet, TinmanError};
+use Tinman::data_and_config::neural_net_config_parts::LayerSettings;
+
+// plain gradient descent with a learning rate of 1, so each value moves by exactly its clipped mean gradient
+fn sgd(mut layer_settings : LayerSettings) -> LayerSettings {
+    layer_settings.learning_rate = 1.0;
+    layer_settings.momentum = 0.0;
+    layer_settings
+}
+
+// every weight and bias of the network as one list
+fn learned_values(nnet : &mut NeuralNetwork) -> Vec<f64> {
+    let mut values : Vec<f64> = Vec::new();
+    for layer_settings in nnet.get_settings().layers {
+        values.extend(layer_settings.layer_weights.unwrap().concat());
+        values.extend(layer_settings.layer_biases.unwrap());
+    }
+    values
+}
+
+// how far each weight and bias moves in one update over the whole dataset
+fn clipped_changes(clip_value : Option<f64>, clip_norm : Option<f64>) -> Vec<f64> {
+    let data : DataSet = dataset(20);
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&settings(vec![sgd(layer("tanh", 4)), sgd(layer("softmax", 3))])).unwrap();
+    let before : Vec<f64> = learned_values(&mut nnet);
+    let rows : Vec<&Vec<f64>> = data.data.iter().map(|row| &row.columns).collect();
+    let targets : Vec<Vec<f64>> = data.data.iter().map(|row| nnet.multi_hot_targets(&[row.label])).collect();
+    nnet.forward_rows(&rows).unwrap();
+    nnet.set_error_deltas(&targets).unwrap();
+    nnet.accumulate_gradients_for_rows(&rows).unwrap();
+    nnet.clip_gradients(clip_value, clip_norm);
+    nnet.apply_gradients();
+    before.iter().zip(learned_values(&mut nnet)).map(|(before, after)| after - before).collect()
+}
+
+fn norm(values : &[f64]) -> f64 {
+    values.iter().map(|value| value * value).sum::<f64>().sqrt()
+}
+
+#[test]
+fn clip_value_limits_each_change() {
+    let unclipped : Vec<f64> = clipped_changes(None, None);
+    let limit : f64 = 0.01;
+    assert!(unclipped.iter().any(|change| change.abs() > 2.0 * limit), "nothing to clip");
+    let clipped : Vec<f64> = clipped_changes(Some(limit), None);
+    for (clipped, unclipped) in clipped.iter().zip(&unclipped) {
+        assert!(clipped.abs() <= limit + 1e-12, "change {} past {}", clipped, limit);
+        // values under the limit pass through untouched
+        if unclipped.abs() < limit {
+            assert!((clipped - unclipped).abs() < 1e-12);
+        }
+    }
+}
+
+#[test]
+fn clip_norm_scales_the_whole_gradient() {
+    let unclipped : Vec<f64> = clipped_changes(None, None);
+    let limit : f64 = norm(&unclipped) / 4.0;
+    let clipped : Vec<f64> = clipped_changes(None, Some(limit));
+    assert!((norm(&clipped) - limit).abs() < 1e-9, "norm {} instead of {}", norm(&clipped), limit);
+    // the direction stays the same
+    for (clipped, unclipped) in clipped.iter().zip(&unclipped) {
+        assert!((clipped * 4.0 - unclipped).abs() < 1e-9);
+    }
+    // a limit above the norm changes nothing, and one that isn't above 0 is ignored
+    assert_eq!(clipped_changes(None, Some(norm(&unclipped) * 2.0)), unclipped);
+    assert_eq!(clipped_changes(Some(0.0), Some(-1.0)), unclipped);
+}
+
+// a regression network whose learning rate sends the weights to infinity
+fn diverging_settings() -> NeuralNetSettings {
+    let mut nnet_settings : NeuralNetSettings = settings(vec![layer("lrelu", 16), layer("linear", 2)]);
+    nnet_settings.task = Some("regression".to_string());
+    for layer_settings in nnet_settings.layers.iter_mut() {
+        layer_settings.learning_rate = 5.0;
+    }
+    nnet_settings
+}
+
+#[test]
+fn divergence_stops_training_and_fails_the_test() {
+    let data : DataSet = dataset(60);
+    let mut trainer : NNetTrainer = NNetTrainer::new(&diverging_settings(), 50, 20).unwrap();
+    trainer.train(&data).unwrap();
+    assert!(trainer.has_diverged());
+    assert!(trainer.get_train_losses().len() < 20);
+    trainer.test(&data).unwrap();
+    assert_eq!(trainer.get_test_result(), 0.0);
+    assert_eq!(trainer.get_test_loss(), f64::INFINITY);
+}
+
+#[test]
+fn failed_trainer_is_scored_like_a_diverged_one_until_trained_again() {
+    let data : DataSet = dataset(60);
+    let mut trainer : NNetTrainer = NNetTrainer::new(&settings(vec![layer("tanh", 6), layer("softmax", 3)]), 50, 3).unwrap();
+    trainer.train(&data).unwrap();
+    trainer.mark_failed();
+    assert!(trainer.has_diverged());
+    assert_eq!(trainer.get_test_result(), 0.0);
+    assert_eq!(trainer.get_test_loss(), f64::INFINITY);
+    trainer.train(&data).unwrap();
+    trainer.test(&data).unwrap();
+    assert!(!trainer.has_diverged());
+    assert!(trainer.get_test_loss().is_finite());
+}
+
+#[test]
+fn clipping_keeps_a_large_learning_rate_from_diverging() {
+    let data : DataSet = dataset(60);
+    let mut trainer : NNetTrainer = NNetTrainer::new(&diverging_settings(), 50, 20).unwrap();
+    trainer.set_clip_value(Some(0.5));
+    trainer.set_clip_norm(Some(0.1));
+    trainer.train(&data).unwrap();
+    assert!(!trainer.has_diverged());
+    assert_eq!(trainer.get_train_losses().len(), 20);
+    trainer.test(&data).unwrap();
+    assert!(trainer.get_test_result().is_finite());
+    assert!(trainer.get_test_loss().is_finite());
+}
+
+#[test]
+fn empty_test_split_is_an_error() {
+    let data : DataSet = dataset(40);
+    let mut trainer : NNetTrainer = NNetTrainer::new(&settings(vec![layer("tanh", 4), layer("softmax", 3)]), 40, 2).unwrap();
+    trainer.train(&data).unwrap();
+    assert_eq!(trainer.get_test_losses().len(), 0);
+    assert!(matches!(trainer.test(&data), Err(TinmanError::NoTestRows)));
+    assert_eq!(trainer.get_test_result(), 0.0);
+}
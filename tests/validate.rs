@@ -0,0 +1,221 @@
+mod common;
+
+use common::{layer, settings, dataset};
+use Tinman::{validate, DataSet, NeuralNetSettings, NeuralNetwork, TinmanError};
+use Tinman::data_and_config::neural_net_config_parts::{LayerSettings, ScheduleSettings};
+
+// a valid tanh + softmax classifier for the test data
+fn good_settings() -> NeuralNetSettings {
+    settings(vec![layer("tanh", 4), layer("softmax", 3)])
+}
+
+fn errors(nnet_settings : &NeuralNetSettings, data : &DataSet) -> Vec<TinmanError> {
+    match validate(nnet_settings, data) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+    }
+}
+
+// validates with the first layer changed, expecting exactly one error
+fn first_layer_error(change : impl Fn(&mut LayerSettings)) -> TinmanError {
+    let mut nnet_settings : NeuralNetSettings = good_settings();
+    change(&mut nnet_settings.layers[0]);
+    let mut found : Vec<TinmanError> = errors(&nnet_settings, &dataset(10));
+    assert_eq!(found.len(), 1, "{:?}", found);
+    found.remove(0)
+}
+
+fn settings_error(change : impl Fn(&mut NeuralNetSettings)) -> TinmanError {
+    let mut nnet_settings : NeuralNetSettings = good_settings();
+    change(&mut nnet_settings);
+    let mut found : Vec<TinmanError> = errors(&nnet_settings, &dataset(10));
+    assert_eq!(found.len(), 1, "{:?}", found);
+    found.remove(0)
+}
+
+#[test]
+fn good_settings_and_data_pass() {
+    assert!(validate(&good_settings(), &dataset(10)).is_ok());
+}
+
+#[test]
+fn rejects_unknown_task_precision_and_loss() {
+    let error = settings_error(|s| s.task = Some("clustering".to_string()));
+    assert!(matches!(error, TinmanError::UnknownTask { ref name } if name == "clustering"));
+    let error = settings_error(|s| s.precision = Some("f16".to_string()));
+    assert!(matches!(error, TinmanError::UnknownPrecision { ref name } if name == "f16"));
+    let error = settings_error(|s| s.loss = Some("poisson".to_string()));
+    assert!(matches!(error, TinmanError::UnknownLoss { ref name } if name == "poisson"));
+}
+
+#[test]
+fn unknown_loss_is_an_error_when_building_or_updating() {
+    let mut poisson : NeuralNetSettings = good_settings();
+    poisson.loss = Some("poisson".to_string());
+    assert!(matches!(NeuralNetwork::new(&poisson), Err(TinmanError::UnknownLoss { ref name }) if name == "poisson"));
+
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&good_settings()).unwrap();
+    let loss : Option<String> = nnet.get_settings().loss;
+    assert!(matches!(nnet.update_state(&poisson), Err(TinmanError::UnknownLoss { .. })));
+    // the network keeps the loss it had
+    assert_eq!(nnet.get_settings().loss, loss);
+}
+
+#[test]
+fn rejects_no_layers() {
+    let error = settings_error(|s| s.layers.clear());
+    assert!(matches!(error, TinmanError::NoLayers));
+}
+
+#[test]
+fn rejects_softmax_before_the_output_layer() {
+    let hidden_softmax : NeuralNetSettings = settings(vec![layer("softmax", 4), layer("softmax", 3)]);
+    let found : Vec<TinmanError> = errors(&hidden_softmax, &dataset(10));
+    assert!(matches!(found[..], [TinmanError::HiddenSoftmax { layer : 0 }]), "{:?}", found);
+    assert!(matches!(NeuralNetwork::new(&hidden_softmax), Err(TinmanError::HiddenSoftmax { layer : 0 })));
+
+    let mut nnet : NeuralNetwork = NeuralNetwork::new(&good_settings()).unwrap();
+    let mut changed : NeuralNetSettings = good_settings();
+    changed.layers[0].activation_function = "softmax".to_string();
+    assert!(matches!(nnet.update_state(&changed), Err(TinmanError::HiddenSoftmax { layer : 0 })));
+}
+
+#[test]
+fn rejects_empty_layer() {
+    let mut nnet_settings : NeuralNetSettings = good_settings();
+    nnet_settings.layers[0].output_units = 0;
+    let found : Vec<TinmanError> = errors(&nnet_settings, &dataset(10));
+    assert!(found.iter().any(|error| matches!(error, TinmanError::EmptyLayer { layer : 0 })));
+}
+
+#[test]
+fn rejects_bad_weight_range() {
+    let error = first_layer_error(|l| l.weight_range = [0.5, -0.5]);
+    assert!(matches!(error, TinmanError::InvalidWeightRange { layer : 0, .. }));
+    let error = first_layer_error(|l| l.weight_range = [std::f64::NAN, 0.5]);
+    assert!(matches!(error, TinmanError::InvalidWeightRange { layer : 0, .. }));
+}
+
+#[test]
+fn rejects_dropout_rate_outside_zero_to_one() {
+    for rate in &[-0.1, 1.0, 1.5, std::f64::NAN] {
+        let error = first_layer_error(|l| l.dropout_rate = Some(*rate));
+        assert!(matches!(error, TinmanError::InvalidDropoutRate { layer : 0, .. }));
+    }
+    let mut nnet_settings : NeuralNetSettings = good_settings();
+    nnet_settings.layers[0].dropout_rate = Some(0.0);
+    nnet_settings.layers[1].dropout_rate = Some(0.5);
+    assert!(errors(&nnet_settings, &dataset(10)).is_empty());
+}
+
+#[test]
+fn rejects_unknown_names_in_a_layer() {
+    let error = first_layer_error(|l| l.activation_function = "swoosh".to_string());
+    assert!(matches!(error, TinmanError::UnknownActivation { layer : 0, .. }));
+    let error = first_layer_error(|l| l.optimizer = Some("adamax".to_string()));
+    assert!(matches!(error, TinmanError::UnknownOptimizer { layer : 0, .. }));
+    let error = first_layer_error(|l| l.init = Some("zeros".to_string()));
+    assert!(matches!(error, TinmanError::UnknownInit { layer : 0, .. }));
+    let error = first_layer_error(|l| l.layer_type = Some("conv".to_string()));
+    assert!(matches!(error, TinmanError::UnknownLayerType { layer : 0, .. }));
+    let error = first_layer_error(|l| l.learning_rate_schedule = Some(ScheduleSettings {
+        schedule : "sawtooth".to_string(),
+        decay_rate : None,
+        step_rounds : None,
+        min_learning_rate : None,
+        patience : None,
+        cycle_multiplier : None
+    }));
+    assert!(matches!(error, TinmanError::UnknownSchedule { layer : 0, .. }));
+}
+
+#[test]
+fn rejects_wrong_activation_parameter_count() {
+    let error = first_layer_error(|l| {
+        l.activation_function = "prelu".to_string();
+        l.activation_parameters = Some(vec![0.1, 0.2]);
+    });
+    assert!(matches!(error, TinmanError::ActivationParameterCountMismatch { layer : 0, expected : 1, actual : 2 }));
+}
+
+#[test]
+fn rejects_wrong_weight_shapes() {
+    let error = first_layer_error(|l| l.layer_weights = Some(vec![vec![0.1, 0.2]; 3]));
+    assert!(matches!(error, TinmanError::WeightVectorCountMismatch { layer : 0, expected : 4, actual : 3 }));
+    let error = first_layer_error(|l| l.layer_weights = Some(vec![vec![0.1, 0.2, 0.3]; 4]));
+    assert!(matches!(error, TinmanError::WeightLengthMismatch { layer : 0, node : 0, expected : 2, actual : 3 }));
+    let error = first_layer_error(|l| l.layer_biases = Some(vec![0.1; 5]));
+    assert!(matches!(error, TinmanError::BiasCountMismatch { layer : 0, expected : 4, actual : 5 }));
+}
+
+#[test]
+fn rejects_bad_batch_norm_layer() {
+    let mut batch_norm : LayerSettings = layer("linear", 3);
+    batch_norm.layer_type = Some("batch_norm".to_string());
+    let mut nnet_settings : NeuralNetSettings = settings(vec![layer("tanh", 4), batch_norm.clone(), layer("softmax", 3)]);
+    let found : Vec<TinmanError> = errors(&nnet_settings, &dataset(10));
+    assert!(matches!(found[..], [TinmanError::BatchNormSizeMismatch { layer : 1, expected : 4, actual : 3 }]), "{:?}", found);
+
+    nnet_settings.layers[1].output_units = 4;
+    nnet_settings.layers[1].running_mean = Some(vec![0.0; 3]);
+    let found : Vec<TinmanError> = errors(&nnet_settings, &dataset(10));
+    assert!(matches!(found[..], [TinmanError::RunningStatsLengthMismatch { layer : 1, expected : 4, actual : 3 }]), "{:?}", found);
+}
+
+#[test]
+fn rejects_rows_that_do_not_fit() {
+    let nnet_settings : NeuralNetSettings = good_settings();
+    let mut data : DataSet = dataset(10);
+    data.data[1].columns.push(0.5);
+    data.data[2].columns[1] = std::f64::INFINITY;
+    data.data[3].label = 3;
+    let found : Vec<TinmanError> = errors(&nnet_settings, &data);
+    assert_eq!(found.len(), 4, "{:?}", found);
+    assert!(matches!(found[0], TinmanError::InputSizeMismatch { row : Some(1), expected : 2, actual : 3 }));
+    assert!(matches!(found[1], TinmanError::NonFiniteFeature { row : 2, column : 1 }));
+    assert!(matches!(found[2], TinmanError::LabelOutOfRange { row : Some(3), label : 3, output_units : 3 }));
+    assert!(matches!(found[3], TinmanError::LabelBeyondResultMap { row : 3, label : 3, classes : 3 }));
+}
+
+#[test]
+fn rejects_labels_beyond_the_result_map() {
+    let nnet_settings : NeuralNetSettings = settings(vec![layer("tanh", 4), layer("softmax", 4)]);
+    let mut data : DataSet = dataset(10);
+    data.data[4].label = 3;
+    let found : Vec<TinmanError> = errors(&nnet_settings, &data);
+    assert!(matches!(found[..], [TinmanError::LabelBeyondResultMap { row : 4, label : 3, classes : 3 }]), "{:?}", found);
+}
+
+#[test]
+fn rejects_bad_regression_targets() {
+    let mut nnet_settings : NeuralNetSettings = settings(vec![layer("tanh", 4), layer("linear", 2)]);
+    nnet_settings.task = Some("regression".to_string());
+    let mut data : DataSet = dataset(10);
+    data.data[0].targets.pop();
+    data.data[5].targets[1] = std::f64::NAN;
+    let found : Vec<TinmanError> = errors(&nnet_settings, &data);
+    assert_eq!(found.len(), 2, "{:?}", found);
+    assert!(matches!(found[0], TinmanError::TargetSizeMismatch { row : Some(0), expected : 2, actual : 1 }));
+    assert!(matches!(found[1], TinmanError::NonFiniteTarget { row : 5, target : 1 }));
+}
+
+#[test]
+fn rejects_multi_label_labels_out_of_range() {
+    let mut nnet_settings : NeuralNetSettings = settings(vec![layer("tanh", 4), layer("sigmoid", 3)]);
+    nnet_settings.task = Some("multi_label".to_string());
+    let mut data : DataSet = dataset(10);
+    data.data[6].labels = vec![0, 3];
+    let found : Vec<TinmanError> = errors(&nnet_settings, &data);
+    assert!(matches!(found[0], TinmanError::LabelOutOfRange { row : Some(6), label : 3, output_units : 3 }), "{:?}", found);
+}
+
+#[test]
+fn reports_every_problem_at_once() {
+    let mut nnet_settings : NeuralNetSettings = good_settings();
+    nnet_settings.loss = Some("poisson".to_string());
+    nnet_settings.layers[0].activation_function = "swoosh".to_string();
+    nnet_settings.layers[1].optimizer = Some("adamax".to_string());
+    let mut data : DataSet = dataset(10);
+    data.data[0].columns.pop();
+    assert_eq!(errors(&nnet_settings, &data).len(), 4);
+}